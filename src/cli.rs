@@ -0,0 +1,1370 @@
+use std::{
+    env, fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::{
+    diagnostic, files,
+    report::{self, Outcome},
+    ser::{EventWriter, IoWriter},
+    to_string, to_string_pretty, Event, Indent, JSONDocument, JSONSerializer, JsonValue, Newline,
+    ParserErrors, ParserOptions, SerializerOptions, Span,
+};
+use thiserror::Error;
+
+const USAGE: &str = "\
+Usage: json_parser [OPTIONS] [COMMAND] [ARGS]...
+
+Commands:
+  validate [FILES]...      check that FILES are valid JSON, the default command
+      -r, --recursive      check every .json file in the directories given
+      -j, --jobs N         check N files at a time
+      --output-format F    report as text, json (a record per file) or sarif
+  fmt [FILES]...           pretty print FILES
+      --indent N           indent with N spaces, 2 by default
+      --tabs               indent with tabs
+      --check              list the files that are not formatted and fail if any are
+      --write              rewrite the files that are not formatted
+  minify [IN]              strip all whitespace from IN
+      -o, --output OUT     write to OUT instead of stdout
+  query PATH [FILE]        print the value at PATH, like `servers.0.name`
+
+Options:
+  -q, --quiet              only print errors and the output asked for
+      --allow-bom          skip a byte order mark at the start of the input
+      --color WHEN         color errors: auto, always or never
+  -h, --help               print this help
+  -V, --version            print the version
+
+FILE, FILES and IN are read from stdin when they are `-` or left out. FILES
+may also be patterns like `fixtures/**/*.json`.";
+
+//how a command fails: with an error in a document, or with the outcome of
+//checking many of them
+#[derive(Error, Debug)]
+pub(crate) enum CliError {
+    #[error(transparent)]
+    Diagnostic(#[from] Diagnostic),
+    #[error("{0} file(s) not formatted")]
+    NotFormatted(usize),
+    #[error("{invalid} of {total} files are not valid json")]
+    InvalidFiles { invalid: usize, total: usize },
+}
+
+impl From<ParserErrors> for CliError {
+    fn from(error: ParserErrors) -> Self {
+        CliError::Diagnostic(error.into())
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> Self {
+        ParserErrors::from(error).into()
+    }
+}
+
+//an error reading or parsing a document, as it is shown: with the annotated
+//lines around it or the name of the file it is about, when there is one
+#[derive(Debug)]
+pub(crate) struct Diagnostic {
+    pub(crate) error: ParserErrors,
+    pub(crate) rendered: Option<String>,
+}
+
+impl From<ParserErrors> for Diagnostic {
+    fn from(error: ParserErrors) -> Self {
+        Diagnostic {
+            error,
+            rendered: None,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.rendered {
+            Some(rendered) => f.write_str(rendered),
+            None => self.error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Diagnostic {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+//what the command line asked for
+#[derive(Debug, PartialEq)]
+pub(crate) struct Config {
+    command: Command,
+    quiet: bool,
+    color: Color,
+    allow_bom: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Validate(ValidateConfig),
+    Format(FormatConfig),
+    Minify {
+        input: Input,
+        //stdout when not given
+        output: Option<String>,
+    },
+    Query {
+        path: String,
+        input: Input,
+    },
+    Help,
+    Version,
+}
+
+//where a command reads its document from
+#[derive(Debug, PartialEq)]
+enum Input {
+    Stdin,
+    File(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum Color {
+    //only when stderr is a terminal and NO_COLOR isn't set
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, PartialEq)]
+struct ValidateConfig {
+    //as given, patterns and directories are expanded when the command runs
+    inputs: Vec<Input>,
+    recursive: bool,
+    //how many files are checked at a time
+    jobs: usize,
+    output_format: OutputFormat,
+}
+
+#[derive(Debug, PartialEq)]
+enum OutputFormat {
+    //a line per file and annotated errors, for people
+    Text,
+    //a JSON object per file and line
+    Json,
+    //one SARIF log for code review tools
+    Sarif,
+}
+
+#[derive(Debug, PartialEq)]
+struct FormatConfig {
+    indent: Indent,
+    mode: FormatMode,
+    files: Vec<Input>,
+}
+
+#[derive(Debug, PartialEq)]
+enum FormatMode {
+    //print the formatted documents
+    Print,
+    //only report the files that aren't formatted yet
+    Check,
+    //rewrite the files that aren't formatted yet
+    Write,
+}
+
+impl Config {
+    //the global options can go anywhere, the first other argument picks the
+    //command. Anything else is a file to validate, as before there were
+    //commands
+    pub(crate) fn build(args: Vec<String>) -> Result<Config, ParserErrors> {
+        let mut quiet = false;
+        let mut color = Color::Auto;
+        let mut allow_bom = false;
+        let mut rest = Vec::new();
+        let mut args = args.into_iter().skip(1);
+        while let Some(arg) = args.next() {
+            let command = match arg.as_str() {
+                "-h" | "--help" => Command::Help,
+                "-V" | "--version" => Command::Version,
+                "-q" | "--quiet" => {
+                    quiet = true;
+                    continue;
+                }
+                "--allow-bom" => {
+                    allow_bom = true;
+                    continue;
+                }
+                "--color" => {
+                    color = Color::build(args.next().as_deref())?;
+                    continue;
+                }
+                _ => {
+                    match arg.strip_prefix("--color=") {
+                        Some(when) => color = Color::build(Some(when))?,
+                        None => rest.push(arg),
+                    }
+                    continue;
+                }
+            };
+            return Ok(Config {
+                command,
+                quiet,
+                color,
+                allow_bom,
+            });
+        }
+
+        let command = match rest.first().map(String::as_str) {
+            Some("validate") => Command::build_validate(&rest[1..])?,
+            Some("fmt") => Command::Format(FormatConfig::build(&rest[1..])?),
+            Some("minify") => Command::build_minify(&rest[1..])?,
+            Some("query") => Command::build_query(&rest[1..])?,
+            _ => Command::build_validate(&rest)?,
+        };
+        Ok(Config {
+            command,
+            quiet,
+            color,
+            allow_bom,
+        })
+    }
+}
+
+impl Command {
+    //json_parser validate [-r] [-j N] [--output-format F] [FILES]...
+    fn build_validate(args: &[String]) -> Result<Command, ParserErrors> {
+        let mut inputs = Vec::new();
+        let mut recursive = false;
+        let mut jobs = 1;
+        let mut output_format = OutputFormat::Text;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-r" | "--recursive" => recursive = true,
+                "-j" | "--jobs" => {
+                    let count = args.next().and_then(|count| count.parse().ok());
+                    let Some(count @ 1..) = count else {
+                        return Err(ParserErrors::ArgumentError(format!(
+                            "{arg} needs a number of files above 0"
+                        )));
+                    };
+                    jobs = count;
+                }
+                "--output-format" => {
+                    output_format = OutputFormat::build(args.next().map(String::as_str))?;
+                }
+                _ if arg.starts_with("--output-format=") => {
+                    output_format = OutputFormat::build(arg.strip_prefix("--output-format="))?;
+                }
+                _ => {
+                    check_positional(arg)?;
+                    inputs.push(Input::new(arg));
+                }
+            }
+        }
+        if inputs.is_empty() {
+            inputs.push(Input::Stdin);
+        }
+        Ok(Command::Validate(ValidateConfig {
+            inputs,
+            recursive,
+            jobs,
+            output_format,
+        }))
+    }
+
+    //json_parser minify [IN] [-o OUT]
+    fn build_minify(args: &[String]) -> Result<Command, ParserErrors> {
+        let mut input = None;
+        let mut output = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--output" => {
+                    let Some(path) = args.next() else {
+                        return Err(ParserErrors::ArgumentError(format!(
+                            "{arg} needs a filename"
+                        )));
+                    };
+                    output = Some(path.clone());
+                }
+                _ if input.is_some() => {
+                    check_positional(arg)?;
+                    return Err(ParserErrors::ArgumentError(
+                        "too many arguments".to_string(),
+                    ));
+                }
+                _ => {
+                    check_positional(arg)?;
+                    input = Some(Input::new(arg));
+                }
+            }
+        }
+        Ok(Command::Minify {
+            input: input.unwrap_or(Input::Stdin),
+            output,
+        })
+    }
+
+    //json_parser query PATH [FILE]
+    fn build_query(args: &[String]) -> Result<Command, ParserErrors> {
+        for arg in args {
+            check_positional(arg)?;
+        }
+        match args {
+            [] => Err(ParserErrors::ArgumentError(
+                "please provide a path to query".to_string(),
+            )),
+            [path] => Ok(Command::Query {
+                path: path.clone(),
+                input: Input::Stdin,
+            }),
+            [path, file] => Ok(Command::Query {
+                path: path.clone(),
+                input: Input::new(file),
+            }),
+            _ => Err(ParserErrors::ArgumentError(
+                "too many arguments".to_string(),
+            )),
+        }
+    }
+}
+
+//a lone `-` stands for stdin, anything else starting with one is an option
+//the command doesn't know
+fn check_positional(arg: &str) -> Result<(), ParserErrors> {
+    if arg.starts_with('-') && arg != "-" {
+        return Err(ParserErrors::ArgumentError(format!(
+            "unknown option `{arg}`"
+        )));
+    }
+    Ok(())
+}
+
+impl Input {
+    fn new(arg: &str) -> Input {
+        match arg {
+            "-" => Input::Stdin,
+            path => Input::File(path.to_string()),
+        }
+    }
+
+    //how errors refer to the input
+    fn name(&self) -> &str {
+        match self {
+            Input::Stdin => "<stdin>",
+            Input::File(path) => path,
+        }
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Diagnostic> {
+        let read = match self {
+            Input::Stdin => {
+                let mut input = Vec::new();
+                io::stdin().lock().read_to_end(&mut input).map(|_| input)
+            }
+            Input::File(path) => fs::read(path),
+        };
+        read.map_err(|error| self.io_error(error))
+    }
+
+    //io errors don't say which file they are about, so they get its name
+    fn io_error(&self, error: io::Error) -> Diagnostic {
+        let error = ParserErrors::IoError(error);
+        Diagnostic {
+            rendered: Some(format!("{}: {error}", self.name())),
+            error,
+        }
+    }
+}
+
+impl Color {
+    fn build(when: Option<&str>) -> Result<Color, ParserErrors> {
+        match when {
+            Some("auto") => Ok(Color::Auto),
+            Some("always") => Ok(Color::Always),
+            Some("never") => Ok(Color::Never),
+            _ => Err(ParserErrors::ArgumentError(
+                "--color needs one of auto, always or never".to_string(),
+            )),
+        }
+    }
+}
+
+impl OutputFormat {
+    fn build(format: Option<&str>) -> Result<OutputFormat, ParserErrors> {
+        match format {
+            Some("text") => Ok(OutputFormat::Text),
+            Some("json") => Ok(OutputFormat::Json),
+            Some("sarif") => Ok(OutputFormat::Sarif),
+            _ => Err(ParserErrors::ArgumentError(
+                "--output-format needs one of text, json or sarif".to_string(),
+            )),
+        }
+    }
+}
+
+impl FormatConfig {
+    //json_parser fmt [--indent N] [--tabs] [--check] [--write] [FILES]...
+    fn build(args: &[String]) -> Result<FormatConfig, ParserErrors> {
+        let mut indent = None;
+        let mut mode = FormatMode::Print;
+        let mut files = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (set_indent, set_mode) = match arg.as_str() {
+                "--indent" => {
+                    let width = args.next().and_then(|width| width.parse().ok());
+                    let Some(width) = width else {
+                        return Err(ParserErrors::ArgumentError(
+                            "--indent needs a number of spaces".to_string(),
+                        ));
+                    };
+                    (Some(Indent::Spaces(width)), None)
+                }
+                "--tabs" => (Some(Indent::Tab), None),
+                "--check" => (None, Some(FormatMode::Check)),
+                "--write" => (None, Some(FormatMode::Write)),
+                _ => {
+                    check_positional(arg)?;
+                    files.push(Input::new(arg));
+                    continue;
+                }
+            };
+            if set_indent.is_some() {
+                if indent.is_some() {
+                    return Err(ParserErrors::ArgumentError(
+                        "--indent and --tabs can only be given once".to_string(),
+                    ));
+                }
+                indent = set_indent;
+            }
+            if let Some(set_mode) = set_mode {
+                if mode != FormatMode::Print {
+                    return Err(ParserErrors::ArgumentError(
+                        "--check and --write can only be given once".to_string(),
+                    ));
+                }
+                mode = set_mode;
+            }
+        }
+        if files.is_empty() {
+            files.push(Input::Stdin);
+        }
+        if mode == FormatMode::Write && files.contains(&Input::Stdin) {
+            return Err(ParserErrors::ArgumentError(
+                "--write can't rewrite stdin".to_string(),
+            ));
+        }
+        Ok(FormatConfig {
+            indent: indent.unwrap_or(Indent::Spaces(2)),
+            mode,
+            files,
+        })
+    }
+}
+
+//how the commands report back, resolved from the global options
+struct Reporter {
+    quiet: bool,
+    color: bool,
+    //whether errors get the lines around them, which reports that only take
+    //the error itself can do without
+    annotate: bool,
+}
+
+impl Reporter {
+    //wraps the error with the annotated lines of `source` around it
+    fn diagnose(&self, error: ParserErrors, source: impl BufRead, path: &str) -> Diagnostic {
+        if !self.annotate {
+            return error.into();
+        }
+        Diagnostic {
+            rendered: Some(diagnostic::render(&error, source, path, self.color)),
+            error,
+        }
+    }
+
+    //reads the lines around the error back from the file it was found in.
+    //Stdin can't be read again, so its errors stay as they are, and so do io
+    //errors, which have no lines to show
+    fn diagnose_input(&self, error: ParserErrors, input: &Input) -> Diagnostic {
+        if !self.annotate || matches!(error, ParserErrors::IoError(_)) {
+            return error.into();
+        }
+        let Input::File(path) = input else {
+            return error.into();
+        };
+        match File::open(path) {
+            Ok(file) => self.diagnose(error, BufReader::new(file), path),
+            Err(_) => self.diagnose(error, io::empty(), path),
+        }
+    }
+}
+
+//runs the command in `args`. Output cut short by a closed pipe, as in
+//`json_parser -h | head -1`, is not an error
+pub(crate) fn run(args: Vec<String>) -> Result<(), CliError> {
+    match run_command(args) {
+        Err(CliError::Diagnostic(Diagnostic {
+            error: ParserErrors::IoError(error),
+            ..
+        })) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+fn run_command(args: Vec<String>) -> Result<(), CliError> {
+    let config = Config::build(args)?;
+    let reporter = Reporter {
+        quiet: config.quiet,
+        color: match config.color {
+            Color::Auto => io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            Color::Always => true,
+            Color::Never => false,
+        },
+        annotate: true,
+    };
+    //what every command starts from when it reads documents
+    let options = ParserOptions {
+        allow_bom: config.allow_bom,
+        ..Default::default()
+    };
+    match &config.command {
+        Command::Validate(validate_config) => validate(validate_config, &options, &reporter),
+        Command::Format(format_config) => format(format_config, &options, &reporter),
+        Command::Minify { input, output } => minify(input, output.as_deref(), &options, &reporter),
+        Command::Query { path, input } => query(path, input, &options, &reporter),
+        Command::Help => {
+            writeln!(io::stdout().lock(), "{USAGE}")?;
+            Ok(())
+        }
+        Command::Version => {
+            let (name, version) = (env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            writeln!(io::stdout().lock(), "{name} {version}")?;
+            Ok(())
+        }
+    }
+}
+
+//one file is checked the way it always was. Given more, every one of them
+//gets a line and the failures are counted up at the end. The json and sarif
+//formats always report that way, leaving out the annotated errors
+fn validate(
+    config: &ValidateConfig,
+    options: &ParserOptions,
+    reporter: &Reporter,
+) -> Result<(), CliError> {
+    let Expanded {
+        mut inputs,
+        unreadable,
+    } = expand_inputs(&config.inputs, config.recursive)?;
+    if let ([input], [], OutputFormat::Text) = (&inputs[..], &unreadable[..], &config.output_format)
+    {
+        validate_input(input, options, reporter)?;
+        if !reporter.quiet {
+            writeln!(io::stdout().lock(), "valid json!")?;
+        }
+        return Ok(());
+    }
+
+    let reporter = &Reporter {
+        annotate: config.output_format == OutputFormat::Text,
+        ..*reporter
+    };
+    let mut results = validate_inputs(&inputs, config.jobs, options, reporter);
+    //directories that couldn't be read fail like files would
+    for (input, error) in unreadable {
+        inputs.push(input);
+        results.push(Err(error));
+    }
+    let outcomes: Vec<Outcome> = inputs
+        .iter()
+        .zip(&results)
+        .map(|(input, result)| Outcome {
+            path: input.name(),
+            result,
+        })
+        .collect();
+    let mut stdout = io::stdout().lock();
+    match config.output_format {
+        OutputFormat::Text => report_text(&mut stdout, &outcomes, reporter)?,
+        OutputFormat::Json => {
+            for outcome in &outcomes {
+                writeln!(stdout, "{}", to_string(&report::json_record(outcome)))?;
+            }
+        }
+        OutputFormat::Sarif => {
+            let log = report::sarif_log(&outcomes);
+            writeln!(stdout, "{}", to_string_pretty(&log))?;
+        }
+    }
+
+    let invalid = results.iter().filter(|result| result.is_err()).count();
+    if invalid > 0 {
+        return Err(CliError::InvalidFiles {
+            invalid,
+            total: inputs.len(),
+        });
+    }
+    if config.output_format == OutputFormat::Text && !reporter.quiet {
+        writeln!(stdout, "{} files are valid json", inputs.len())?;
+    }
+    Ok(())
+}
+
+fn report_text(
+    output: &mut impl Write,
+    outcomes: &[Outcome],
+    reporter: &Reporter,
+) -> io::Result<()> {
+    for outcome in outcomes {
+        match outcome.result {
+            Ok(()) if !reporter.quiet => writeln!(output, "{}: valid", outcome.path)?,
+            Ok(()) => (),
+            //rendered errors already name the file
+            Err(error) if error.rendered.is_some() => eprintln!("{error}"),
+            Err(error) => eprintln!("{}: {error}", outcome.path),
+        }
+    }
+    Ok(())
+}
+
+//the inputs to validate once patterns and directories are expanded
+struct Expanded {
+    inputs: Vec<Input>,
+    //directories that couldn't be read, with the error
+    unreadable: Vec<(Input, Diagnostic)>,
+}
+
+//replaces patterns by the files they match and, with `recursive`,
+//directories by the .json files in them
+fn expand_inputs(inputs: &[Input], recursive: bool) -> Result<Expanded, ParserErrors> {
+    let mut expanded = Vec::new();
+    let mut unreadable = Vec::new();
+    for input in inputs {
+        let Input::File(path) = input else {
+            expanded.push(Input::Stdin);
+            continue;
+        };
+        let glob = files::is_glob(path);
+        let before = (expanded.len(), unreadable.len());
+        let paths = if glob {
+            files::expand_glob(path, &mut unreadable)
+        } else {
+            vec![PathBuf::from(path)]
+        };
+        for path in &paths {
+            if !path.is_dir() {
+                expanded.push(Input::File(path.to_string_lossy().into_owned()));
+            } else if recursive {
+                let files = files::json_files(path, &mut unreadable);
+                expanded.extend(
+                    files
+                        .iter()
+                        .map(|path| Input::File(path.to_string_lossy().into_owned())),
+                );
+            } else if !glob {
+                //directories a pattern happens to match are left out
+                return Err(ParserErrors::ArgumentError(format!(
+                    "{} is a directory, use --recursive to check the files in it",
+                    path.display()
+                )));
+            }
+        }
+        //an argument that checks nothing is most likely a mistake, which
+        //shouldn't pass as all files being valid
+        if (expanded.len(), unreadable.len()) == before {
+            return Err(ParserErrors::ArgumentError(if glob {
+                format!("no files match `{path}`")
+            } else {
+                format!("no .json files in {path}")
+            }));
+        }
+    }
+    let unreadable = unreadable
+        .into_iter()
+        .map(|(path, error)| {
+            let input = Input::File(path.to_string_lossy().into_owned());
+            let error = input.io_error(error);
+            (input, error)
+        })
+        .collect();
+    Ok(Expanded {
+        inputs: expanded,
+        unreadable,
+    })
+}
+
+//checks the inputs on `jobs` threads, each taking the next unchecked input
+//until none are left. The results come back in the order of the inputs
+fn validate_inputs(
+    inputs: &[Input],
+    jobs: usize,
+    options: &ParserOptions,
+    reporter: &Reporter,
+) -> Vec<Result<(), Diagnostic>> {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, Result<(), Diagnostic>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.min(inputs.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(input) = inputs.get(index) else {
+                            return results;
+                        };
+                        results.push((index, validate_input(input, options, reporter)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+fn validate_input(
+    input: &Input,
+    options: &ParserOptions,
+    reporter: &Reporter,
+) -> Result<(), Diagnostic> {
+    //nothing is built, so numbers needn't fit a float and there's no
+    //need for a depth limit
+    let json_document = JSONDocument::new(ParserOptions {
+        arbitrary_precision: true,
+        max_depth: None,
+        ..options.clone()
+    });
+    match input {
+        //the file is validated as it is read, and only read again for the
+        //lines around an error
+        Input::File(path) => {
+            let file = File::open(path).map_err(|error| input.io_error(error))?;
+            json_document
+                .validate_reader(file)
+                .map_err(|error| match error {
+                    ParserErrors::IoError(error) => input.io_error(error),
+                    error => reporter.diagnose_input(error, input),
+                })
+        }
+        //stdin is kept in memory instead, to have those lines at hand
+        Input::Stdin => {
+            let source = input.read()?;
+            json_document
+                .validate_reader(&source[..])
+                .map_err(|error| reporter.diagnose(error, &source[..], input.name()))
+        }
+    }
+}
+
+fn format(
+    config: &FormatConfig,
+    parser_options: &ParserOptions,
+    reporter: &Reporter,
+) -> Result<(), CliError> {
+    let options = SerializerOptions {
+        indent: Some(config.indent),
+        ..SerializerOptions::pretty()
+    };
+    let mut unformatted = 0;
+    let mut stdout = io::stdout().lock();
+    for input in &config.files {
+        let source = input.read()?;
+        let formatted = format_document(&source, parser_options, &options)
+            .map_err(|error| reporter.diagnose(error, &source[..], input.name()))?;
+        let changed = formatted.as_bytes() != source;
+        match (&config.mode, input) {
+            (FormatMode::Print, _) => stdout.write_all(formatted.as_bytes())?,
+            (FormatMode::Check, _) if changed => {
+                if !reporter.quiet {
+                    writeln!(stdout, "{} is not formatted", input.name())?;
+                }
+                unformatted += 1;
+            }
+            (FormatMode::Write, Input::File(path)) if changed => {
+                fs::write(path, formatted).map_err(|error| input.io_error(error))?
+            }
+            (FormatMode::Check | FormatMode::Write, _) => (),
+        }
+    }
+
+    if config.mode == FormatMode::Check && unformatted > 0 {
+        return Err(CliError::NotFormatted(unformatted));
+    }
+    Ok(())
+}
+
+//pretty prints a document ending in a line break. Numbers are read with
+//arbitrary precision so they keep their spelling
+fn format_document(
+    input: &[u8],
+    parser_options: &ParserOptions,
+    options: &SerializerOptions,
+) -> Result<String, ParserErrors> {
+    let json_document = JSONDocument::new(ParserOptions {
+        arbitrary_precision: true,
+        ..parser_options.clone()
+    });
+    let value = json_document.parse_slice(input)?;
+    let mut formatted = JSONSerializer::new(options.clone()).to_string(&value);
+    formatted.push_str(match options.newline {
+        Newline::Lf => "\n",
+        Newline::CrLf => "\r\n",
+    });
+    Ok(formatted)
+}
+
+//streams the document into the output without building it in memory, then
+//reports how much smaller it got
+fn minify(
+    input: &Input,
+    output: Option<&str>,
+    options: &ParserOptions,
+    reporter: &Reporter,
+) -> Result<(), CliError> {
+    //numbers are copied as written, and as nothing is built there is no
+    //reason to limit the depth
+    let json_document = JSONDocument::new(ParserOptions {
+        arbitrary_precision: true,
+        max_depth: None,
+        ..options.clone()
+    });
+    let mut source: Counting<Box<dyn Read>> = Counting {
+        inner: match input {
+            Input::Stdin => Box::new(io::stdin().lock()),
+            Input::File(path) => Box::new(File::open(path).map_err(|error| input.io_error(error))?),
+        },
+        count: 0,
+    };
+    let events = json_document.events_reader(&mut source);
+
+    let written = match output {
+        Some(path) => {
+            //the document goes to a file next to the output that only
+            //replaces it once all of it is written, so an invalid input
+            //leaves the output as it was. That also makes the output being
+            //the input harmless
+            let temporary = temporary_path(Path::new(path));
+            let written = File::create(&temporary)
+                .map_err(ParserErrors::from)
+                .and_then(|file| write_compact(events, BufWriter::new(file)))
+                .and_then(|written| {
+                    fs::rename(&temporary, path)?;
+                    Ok(written)
+                });
+            if written.is_err() {
+                let _ = fs::remove_file(&temporary);
+            }
+            written
+        }
+        None => write_compact(events, BufWriter::new(io::stdout().lock())),
+    };
+    let output_size = written.map_err(|error| reporter.diagnose_input(error, input))?;
+
+    if !reporter.quiet {
+        eprintln!(
+            "{}: {} -> {output_size} bytes, saved {}",
+            input.name(),
+            source.count,
+            source.count.saturating_sub(output_size)
+        );
+    }
+    Ok(())
+}
+
+//a hidden name in the same directory as `path`, for a file to rename over it
+fn temporary_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{}.tmp", process::id()))
+}
+
+//writes the events without any whitespace, returning how many bytes that took
+fn write_compact<'a>(
+    events: impl Iterator<Item = Result<(Event<'a>, Span), ParserErrors>>,
+    output: impl Write,
+) -> Result<u64, ParserErrors> {
+    let options = SerializerOptions::compact();
+    let mut output = Counting {
+        inner: output,
+        count: 0,
+    };
+    let mut writer = IoWriter::new(&mut output);
+    let mut result = Ok(());
+    let mut events_writer = EventWriter::new(&mut writer, &options);
+    for event in events {
+        let (event, _) = event?;
+        result = events_writer.write_event(&event);
+        if result.is_err() {
+            break;
+        }
+    }
+    writer.finish(result)?;
+    Ok(output.count)
+}
+
+//counts the bytes read or written through it
+struct Counting<T> {
+    inner: T,
+    count: u64,
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn query(
+    path: &str,
+    input: &Input,
+    options: &ParserOptions,
+    reporter: &Reporter,
+) -> Result<(), CliError> {
+    let source = input.read()?;
+    let json_document = JSONDocument::new(ParserOptions {
+        arbitrary_precision: true,
+        ..options.clone()
+    });
+    let document = json_document
+        .parse_slice(&source)
+        .map_err(|error| reporter.diagnose(error, &source[..], input.name()))?;
+    let Some(value) = select(&document, path) else {
+        return Err(ParserErrors::ArgumentError(format!(
+            "nothing at `{path}` in {}",
+            input.name()
+        ))
+        .into());
+    };
+    writeln!(io::stdout().lock(), "{}", to_string_pretty(value))?;
+    Ok(())
+}
+
+//follows a dotted path like `servers.0.name`, numbers indexing lists and
+//anything else naming object members. An empty path is the whole document
+fn select<'v, 'a>(value: &'v JsonValue<'a>, path: &str) -> Option<&'v JsonValue<'a>> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |value, segment| match value {
+            JsonValue::Array(elements) => elements.get(segment.parse::<usize>().ok()?),
+            _ => value.get(segment),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{files::tests::TempDir, from_str};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        std::iter::once("json_parser")
+            .chain(args.iter().copied())
+            .map(String::from)
+            .collect()
+    }
+
+    fn command(arguments: &[&str]) -> Command {
+        Config::build(args(arguments)).unwrap().command
+    }
+
+    fn argument_error(arguments: &[&str]) -> String {
+        Config::build(args(arguments)).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_config_build() {
+        let file = || Input::File("data.json".into());
+        let validate = |inputs| {
+            Command::Validate(ValidateConfig {
+                inputs,
+                recursive: false,
+                jobs: 1,
+                output_format: OutputFormat::Text,
+            })
+        };
+        assert_eq!(command(&["data.json"]), validate(vec![file()]));
+        assert_eq!(command(&["validate", "data.json"]), validate(vec![file()]));
+        assert_eq!(command(&[]), validate(vec![Input::Stdin]));
+        assert_eq!(
+            command(&["validate", "-", "data.json"]),
+            validate(vec![Input::Stdin, file()])
+        );
+
+        assert_eq!(
+            command(&["query", "a.0", "data.json"]),
+            Command::Query {
+                path: "a.0".into(),
+                input: file()
+            }
+        );
+        assert_eq!(argument_error(&["query"]), "please provide a path to query");
+        assert_eq!(command(&["fmt", "--help"]), Command::Help);
+        assert_eq!(command(&["-V"]), Command::Version);
+    }
+
+    #[test]
+    fn test_validate_config() {
+        assert_eq!(
+            command(&["validate", "-r", "fixtures", "--jobs", "8", "*.json"]),
+            Command::Validate(ValidateConfig {
+                inputs: vec![Input::File("fixtures".into()), Input::File("*.json".into())],
+                recursive: true,
+                jobs: 8,
+                output_format: OutputFormat::Text,
+            })
+        );
+        assert_eq!(
+            command(&["--output-format=sarif", "a.json"]),
+            Command::Validate(ValidateConfig {
+                inputs: vec![Input::File("a.json".into())],
+                recursive: false,
+                jobs: 1,
+                output_format: OutputFormat::Sarif,
+            })
+        );
+        assert_eq!(
+            argument_error(&["validate", "--output-format", "xml"]),
+            "--output-format needs one of text, json or sarif"
+        );
+        assert_eq!(
+            argument_error(&["validate", "-j", "0", "a.json"]),
+            "-j needs a number of files above 0"
+        );
+        assert_eq!(
+            argument_error(&["--jobs"]),
+            "--jobs needs a number of files above 0"
+        );
+    }
+
+    #[test]
+    fn test_io_errors_name_the_file() {
+        let missing = std::env::temp_dir().join("json_parser_missing.json");
+        let missing = missing.to_str().unwrap();
+        let error = run(args(&["-q", missing])).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with(&format!("{missing}: IO error: ")));
+        let error = run(args(&["fmt", missing])).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with(&format!("{missing}: IO error: ")));
+    }
+
+    #[test]
+    fn test_validate_many_files() {
+        let root = TempDir::new("validate");
+        fs::create_dir(root.join("nested")).unwrap();
+        for (file, content) in [
+            ("a.json", "[1e400]"),
+            ("b.json", "{}"),
+            ("nested/c.json", "[1,]"),
+            ("nested/d.json", "null"),
+            ("nested/notes.txt", "not json"),
+        ] {
+            fs::write(root.join(file), content).unwrap();
+        }
+        let dir = root.to_str().unwrap();
+        let pattern = root.join("*.json");
+
+        run(args(&[
+            "-q",
+            "validate",
+            "-j",
+            "3",
+            pattern.to_str().unwrap(),
+        ]))
+        .unwrap();
+        let error = run(args(&[
+            "-q",
+            "--color=never",
+            "validate",
+            "-r",
+            "-j",
+            "3",
+            dir,
+        ]))
+        .unwrap_err();
+        assert_eq!(error.to_string(), "1 of 4 files are not valid json");
+
+        let error = run(args(&["validate", dir])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("{dir} is a directory, use --recursive to check the files in it")
+        );
+        let pattern = root.join("*.yaml");
+        let error = run(args(&["validate", pattern.to_str().unwrap()])).unwrap_err();
+        assert!(error.to_string().starts_with("no files match"));
+        let empty = root.join("empty");
+        fs::create_dir(&empty).unwrap();
+        let empty = empty.to_str().unwrap();
+        let error = run(args(&["validate", "-r", empty])).unwrap_err();
+        assert_eq!(error.to_string(), format!("no .json files in {empty}"));
+
+        let expanded = expand_inputs(&[Input::File(dir.into())], true).unwrap();
+        assert!(expanded.unreadable.is_empty());
+        let results = validate_inputs(
+            &expanded.inputs,
+            2,
+            &ParserOptions::default(),
+            &Reporter {
+                quiet: true,
+                color: false,
+                annotate: true,
+            },
+        );
+        let valid: Vec<bool> = results.iter().map(Result::is_ok).collect();
+        assert_eq!(valid, [true, true, false, true]);
+        assert!(matches!(
+            &results[2],
+            Err(Diagnostic {
+                error: ParserErrors::ParsingError { .. },
+                rendered: Some(_)
+            })
+        ));
+        //without annotations the file isn't read again for the lines around
+        //the error
+        let results = validate_inputs(
+            &expanded.inputs,
+            2,
+            &ParserOptions::default(),
+            &Reporter {
+                quiet: true,
+                color: false,
+                annotate: false,
+            },
+        );
+        assert!(matches!(
+            &results[2],
+            Err(Diagnostic {
+                error: ParserErrors::ParsingError { .. },
+                rendered: None
+            })
+        ));
+
+        //validating builds nothing, so it takes any depth
+        let deep = root.join("deep.json");
+        fs::write(&deep, format!("{}{}", "[".repeat(1000), "]".repeat(1000))).unwrap();
+        run(args(&["-q", deep.to_str().unwrap()])).unwrap();
+    }
+
+    #[test]
+    fn test_global_options() {
+        let config = Config::build(args(&["minify", "-q", "in.json", "--color=never"])).unwrap();
+        assert!(config.quiet);
+        assert_eq!(config.color, Color::Never);
+        assert_eq!(
+            config.command,
+            Command::Minify {
+                input: Input::File("in.json".into()),
+                output: None
+            }
+        );
+
+        let config = Config::build(args(&["--color", "always", "data.json"])).unwrap();
+        assert!(!config.quiet);
+        assert_eq!(config.color, Color::Always);
+        assert_eq!(
+            argument_error(&["--color", "sometimes"]),
+            "--color needs one of auto, always or never"
+        );
+        assert_eq!(argument_error(&["--colour"]), "unknown option `--colour`");
+
+        assert!(!config.allow_bom);
+        let config = Config::build(args(&["fmt", "a.json", "--allow-bom"])).unwrap();
+        assert!(config.allow_bom);
+    }
+
+    #[test]
+    fn test_format_config() {
+        assert_eq!(
+            command(&["fmt", "--tabs", "--check", "a.json", "-"]),
+            Command::Format(FormatConfig {
+                indent: Indent::Tab,
+                mode: FormatMode::Check,
+                files: vec![Input::File("a.json".into()), Input::Stdin],
+            })
+        );
+        assert_eq!(
+            command(&["fmt", "--indent", "4"]),
+            Command::Format(FormatConfig {
+                indent: Indent::Spaces(4),
+                mode: FormatMode::Print,
+                files: vec![Input::Stdin],
+            })
+        );
+        for (arguments, message) in [
+            (
+                &["fmt", "a.json", "--indent"][..],
+                "--indent needs a number of spaces",
+            ),
+            (
+                &["fmt", "--indent", "two", "a.json"],
+                "--indent needs a number of spaces",
+            ),
+            (
+                &["fmt", "--tabs", "--indent", "2", "a.json"],
+                "--indent and --tabs can only be given once",
+            ),
+            (
+                &["fmt", "--check", "--write", "a.json"],
+                "--check and --write can only be given once",
+            ),
+            (&["fmt", "--write"], "--write can't rewrite stdin"),
+            (&["fmt", "--fix", "a.json"], "unknown option `--fix`"),
+        ] {
+            assert_eq!(argument_error(arguments), message);
+        }
+    }
+
+    #[test]
+    fn test_minify_config() {
+        assert_eq!(
+            command(&["minify", "big.json", "-o", "small.json"]),
+            Command::Minify {
+                input: Input::File("big.json".into()),
+                output: Some("small.json".into()),
+            }
+        );
+        assert_eq!(
+            command(&["minify"]),
+            Command::Minify {
+                input: Input::Stdin,
+                output: None,
+            }
+        );
+        for (arguments, message) in [
+            (&["minify", "a.json", "b.json"][..], "too many arguments"),
+            (&["minify", "a.json", "-o"], "-o needs a filename"),
+            (&["minify", "-x", "a.json"], "unknown option `-x`"),
+        ] {
+            assert_eq!(argument_error(arguments), message);
+        }
+    }
+
+    #[test]
+    fn test_write_compact() {
+        let input = "{ \"a\" : [ 1.50 , \"caf\\u00e9\" ],\n  \"b\": { } }\n";
+        let mut output = Vec::new();
+        let written =
+            write_compact(JSONDocument::default().events_str(input), &mut output).unwrap();
+        assert_eq!(output, "{\"a\":[1.5,\"café\"],\"b\":{}}".as_bytes());
+        assert_eq!(written, output.len() as u64);
+
+        let mut output = Vec::new();
+        let error =
+            write_compact(JSONDocument::default().events_str("[1, 2,]"), &mut output).unwrap_err();
+        assert_eq!(error.position().unwrap().offset, 6);
+    }
+
+    #[test]
+    fn test_minify_replaces_the_output_only_when_done() {
+        let root = TempDir::new("minify");
+        let (bad, out) = (root.join("bad.json"), root.join("out.json"));
+        fs::write(&bad, "[1,").unwrap();
+        fs::write(&out, "{\"keep\":1}").unwrap();
+        let (bad, out) = (bad.to_str().unwrap(), out.to_str().unwrap());
+
+        assert!(run(args(&["-q", "minify", bad, "-o", out])).is_err());
+        assert_eq!(fs::read_to_string(out).unwrap(), "{\"keep\":1}");
+        //nothing is left behind but the two files
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 2);
+
+        fs::write(out, "[ 1, 2 ]").unwrap();
+        run(args(&["-q", "minify", out, "-o", out])).unwrap();
+        assert_eq!(fs::read_to_string(out).unwrap(), "[1,2]");
+    }
+
+    #[test]
+    fn test_format_document() {
+        let input = b"{\"b\":[1.50,1E+2,{}],\"a\":\"caf\\u00e9\"}";
+        assert_eq!(
+            format_document(
+                input,
+                &ParserOptions::default(),
+                &SerializerOptions::pretty()
+            )
+            .unwrap(),
+            "{\n  \"b\": [\n    1.50,\n    1E+2,\n    {}\n  ],\n  \"a\": \"café\"\n}\n"
+        );
+        assert!(format_document(
+            b"[1,]",
+            &ParserOptions::default(),
+            &SerializerOptions::pretty()
+        )
+        .is_err());
+
+        let with_bom = b"\xEF\xBB\xBF[1]";
+        assert!(format_document(
+            with_bom,
+            &ParserOptions::default(),
+            &SerializerOptions::compact()
+        )
+        .is_err());
+        let allow_bom = ParserOptions {
+            allow_bom: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_document(with_bom, &allow_bom, &SerializerOptions::compact()).unwrap(),
+            "[1]\n"
+        );
+    }
+
+    #[test]
+    fn test_format_check_and_write() {
+        let root = TempDir::new("fmt");
+        let path = root.join("a.json");
+        fs::write(&path, "[1,\n2]").unwrap();
+        let file = path.to_str().unwrap();
+
+        let error = run(args(&["fmt", "--check", file])).unwrap_err();
+        assert_eq!(error.to_string(), "1 file(s) not formatted");
+        run(args(&["fmt", "--write", "--indent", "4", file])).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[\n    1,\n    2\n]\n");
+        run(args(&["-q", "fmt", "--check", "--indent", "4", file])).unwrap();
+    }
+
+    #[test]
+    fn test_select() {
+        let document =
+            from_str(r#"{"servers": [{"name": "a"}, {"name": "b"}], "0": true}"#).unwrap();
+        assert_eq!(
+            select(&document, "servers.1.name"),
+            Some(&JsonValue::String("b".into()))
+        );
+        assert_eq!(select(&document, "0"), Some(&JsonValue::Bool(true)));
+        assert_eq!(select(&document, ""), Some(&document));
+        assert_eq!(select(&document, "servers.2"), None);
+        assert_eq!(select(&document, "servers.name"), None);
+        assert_eq!(select(&document, "missing"), None);
+    }
+}
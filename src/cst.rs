@@ -0,0 +1,592 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{Event, JSONDocument, JsonValue, ParserErrors, Span};
+
+/// A document parsed for editing: every value keeps its original spelling and
+/// the whitespace, commas and colons around it, so writing the tree back with
+/// `to_string` gives the input byte for byte apart from what was changed.
+///
+/// ```
+/// use json_parser::JSONDocument;
+///
+/// let input = "{\n  \"port\": 80,\n  \"name\": \"caf\\u00e9\"\n}\n";
+/// let mut tree = JSONDocument::default().parse_cst(input).unwrap();
+/// tree.root_mut().get_mut("port").unwrap().replace("8080").unwrap();
+/// assert_eq!(
+///     tree.to_string(),
+///     "{\n  \"port\": 8080,\n  \"name\": \"caf\\u00e9\"\n}\n"
+/// );
+/// ```
+///
+/// Writing and dropping a tree don't recurse, so trees of any depth can be
+/// written back. Cloning and comparing them still recurse once per nesting
+/// level.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SyntaxTree<'a> {
+    root: SyntaxNode<'a>,
+    //whitespace after the document
+    trailing: Cow<'a, str>,
+}
+
+/// A value in a [`SyntaxTree`] together with what is written before it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SyntaxNode<'a> {
+    //everything between the previous node and this one: whitespace plus the
+    //comma or colon separating them
+    before: Cow<'a, str>,
+    kind: SyntaxKind<'a>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum SyntaxKind<'a> {
+    //a string, number, boolean or null exactly as written
+    Scalar(Cow<'a, str>),
+    List {
+        elements: Elements<'a>,
+        before_close: Cow<'a, str>,
+    },
+    Object {
+        members: Members<'a>,
+        before_close: Cow<'a, str>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Member<'a> {
+    //the decoded key, for looking members up
+    name: Cow<'a, str>,
+    //the key as written
+    key: SyntaxNode<'a>,
+    value: SyntaxNode<'a>,
+}
+
+//the children of lists and objects. Dropping them empties the nested nodes
+//onto a heap allocated stack, like Array and Object do for JsonValue, so deep
+//trees don't overflow the call stack
+#[derive(Debug, PartialEq, Clone, Default)]
+struct Elements<'a>(Vec<SyntaxNode<'a>>);
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct Members<'a>(Vec<Member<'a>>);
+
+macro_rules! children {
+    ($children:ident, $child:ty) => {
+        impl<'a> $children<'a> {
+            fn into_vec(mut self) -> Vec<$child> {
+                std::mem::take(&mut self.0)
+            }
+        }
+
+        impl<'a> Deref for $children<'a> {
+            type Target = Vec<$child>;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl DerefMut for $children<'_> {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+    };
+}
+
+children!(Elements, SyntaxNode<'a>);
+children!(Members, Member<'a>);
+
+impl Drop for Elements<'_> {
+    fn drop(&mut self) {
+        drop_nested(std::mem::take(&mut self.0));
+    }
+}
+
+impl Drop for Members<'_> {
+    fn drop(&mut self) {
+        //keys are strings, only the values can nest
+        drop_nested(
+            std::mem::take(&mut self.0)
+                .into_iter()
+                .map(|member| member.value),
+        );
+    }
+}
+
+fn is_nested(node: &SyntaxNode) -> bool {
+    match &node.kind {
+        SyntaxKind::Scalar(_) => false,
+        SyntaxKind::List { elements, .. } => !elements.is_empty(),
+        SyntaxKind::Object { members, .. } => !members.is_empty(),
+    }
+}
+
+fn drop_nested<'a>(nodes: impl IntoIterator<Item = SyntaxNode<'a>>) {
+    let mut stack: Vec<SyntaxNode<'a>> = nodes.into_iter().filter(is_nested).collect();
+    while let Some(mut node) = stack.pop() {
+        match &mut node.kind {
+            SyntaxKind::Scalar(_) => (),
+            SyntaxKind::List { elements, .. } => stack.extend(elements.drain(..).filter(is_nested)),
+            SyntaxKind::Object { members, .. } => stack.extend(
+                members
+                    .drain(..)
+                    .map(|member| member.value)
+                    .filter(is_nested),
+            ),
+        }
+    }
+}
+
+impl<'a> SyntaxTree<'a> {
+    pub fn root(&self) -> &SyntaxNode<'a> {
+        &self.root
+    }
+
+    pub fn root_mut(&mut self) -> &mut SyntaxNode<'a> {
+        &mut self.root
+    }
+
+    fn into_owned(self) -> SyntaxTree<'static> {
+        SyntaxTree {
+            root: self.root.into_owned(),
+            trailing: owned(self.trailing),
+        }
+    }
+}
+
+impl<'a> SyntaxNode<'a> {
+    /// Looks up `key` in an object, returning the last member with that name.
+    pub fn get(&self, key: &str) -> Option<&SyntaxNode<'a>> {
+        match &self.kind {
+            SyntaxKind::Object { members, .. } => members
+                .iter()
+                .rev()
+                .find(|member| member.name == key)
+                .map(|member| &member.value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut SyntaxNode<'a>> {
+        match &mut self.kind {
+            SyntaxKind::Object { members, .. } => members
+                .iter_mut()
+                .rev()
+                .find(|member| member.name == key)
+                .map(|member| &mut member.value),
+            _ => None,
+        }
+    }
+
+    /// The element at `index` of a list.
+    pub fn get_index(&self, index: usize) -> Option<&SyntaxNode<'a>> {
+        match &self.kind {
+            SyntaxKind::List { elements, .. } => elements.get(index),
+            _ => None,
+        }
+    }
+
+    pub fn get_index_mut(&mut self, index: usize) -> Option<&mut SyntaxNode<'a>> {
+        match &mut self.kind {
+            SyntaxKind::List { elements, .. } => elements.get_mut(index),
+            _ => None,
+        }
+    }
+
+    /// Replaces the value with the JSON document in `json`, keeping what is
+    /// written around it. The new value is written exactly as given.
+    pub fn replace(&mut self, json: &str) -> Result<(), ParserErrors> {
+        let tree = JSONDocument::default().parse_cst(json)?.into_owned();
+        self.kind = tree.root.kind;
+        Ok(())
+    }
+
+    /// Parses the value into a [`JsonValue`].
+    pub fn to_value(&self) -> Result<JsonValue<'static>, ParserErrors> {
+        let text = self.to_string();
+        let value = JSONDocument::default().parse_str(&text)?;
+        Ok(value.into_owned())
+    }
+
+    //copies the tree with an explicit stack, like JsonValue::into_owned
+    fn into_owned(self) -> SyntaxNode<'static> {
+        let mut stack: Vec<Owning<'a>> = Vec::new();
+        let mut node = self;
+        loop {
+            let before = owned(node.before);
+            let mut done = match node.kind {
+                SyntaxKind::Scalar(text) => Some(SyntaxNode {
+                    before,
+                    kind: SyntaxKind::Scalar(owned(text)),
+                }),
+                SyntaxKind::List {
+                    elements,
+                    before_close,
+                } => {
+                    let copied = Vec::with_capacity(elements.len());
+                    let before_close = owned(before_close);
+                    stack.push(Owning::List(
+                        before,
+                        elements.into_vec().into_iter(),
+                        copied,
+                        before_close,
+                    ));
+                    None
+                }
+                SyntaxKind::Object {
+                    members,
+                    before_close,
+                } => {
+                    let copied = Vec::with_capacity(members.len());
+                    let before_close = owned(before_close);
+                    let members = members.into_vec().into_iter();
+                    stack.push(Owning::Object(before, members, copied, before_close, None));
+                    None
+                }
+            };
+            //hand the finished node to its parent and find the next child
+            loop {
+                let Some(top) = stack.last_mut() else {
+                    return done.expect("a node is done once nothing is open");
+                };
+                if let Some(finished) = done.take() {
+                    top.push(finished);
+                }
+                if let Some(child) = top.next_child() {
+                    node = child;
+                    break;
+                }
+                done = stack.pop().map(Owning::finish);
+            }
+        }
+    }
+
+    //writes the value with an explicit stack, like ser.rs does, so deep trees
+    //don't overflow
+    fn write_value(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        enum Frame<'n, 'a> {
+            List(std::slice::Iter<'n, SyntaxNode<'a>>, &'n str),
+            Object(std::slice::Iter<'n, Member<'a>>, &'n str),
+        }
+        let mut stack: Vec<Frame> = Vec::new();
+        let mut next = Some(self);
+        loop {
+            if let Some(node) = next.take() {
+                match &node.kind {
+                    SyntaxKind::Scalar(text) => f.write_str(text)?,
+                    SyntaxKind::List {
+                        elements,
+                        before_close,
+                    } => {
+                        f.write_str("[")?;
+                        stack.push(Frame::List(elements.iter(), before_close));
+                    }
+                    SyntaxKind::Object {
+                        members,
+                        before_close,
+                    } => {
+                        f.write_str("{")?;
+                        stack.push(Frame::Object(members.iter(), before_close));
+                    }
+                }
+            }
+            match stack.last_mut() {
+                None => return Ok(()),
+                Some(Frame::List(elements, before_close)) => match elements.next() {
+                    Some(element) => {
+                        f.write_str(&element.before)?;
+                        next = Some(element);
+                    }
+                    None => {
+                        write!(f, "{before_close}]")?;
+                        stack.pop();
+                    }
+                },
+                Some(Frame::Object(members, before_close)) => match members.next() {
+                    Some(member) => {
+                        //keys are strings, so writing one doesn't go deeper
+                        write!(f, "{}{}", member.key.before, member.key)?;
+                        f.write_str(&member.value.before)?;
+                        next = Some(&member.value);
+                    }
+                    None => {
+                        write!(f, "{before_close}}}")?;
+                        stack.pop();
+                    }
+                },
+            }
+        }
+    }
+}
+
+//a list or object whose children are being copied by into_owned, with what
+//is written before it and before its closing bracket
+enum Owning<'a> {
+    List(
+        Cow<'static, str>,
+        std::vec::IntoIter<SyntaxNode<'a>>,
+        Vec<SyntaxNode<'static>>,
+        Cow<'static, str>,
+    ),
+    Object(
+        Cow<'static, str>,
+        std::vec::IntoIter<Member<'a>>,
+        Vec<Member<'static>>,
+        Cow<'static, str>,
+        //the name and key of the member whose value is being copied
+        Option<(Cow<'static, str>, SyntaxNode<'static>)>,
+    ),
+}
+
+impl<'a> Owning<'a> {
+    fn next_child(&mut self) -> Option<SyntaxNode<'a>> {
+        match self {
+            Owning::List(_, elements, _, _) => elements.next(),
+            Owning::Object(_, members, _, _, key) => {
+                let member = members.next()?;
+                //a key is a lone string node, so this stays shallow
+                *key = Some((owned(member.name), member.key.into_owned()));
+                Some(member.value)
+            }
+        }
+    }
+
+    fn push(&mut self, node: SyntaxNode<'static>) {
+        match self {
+            Owning::List(_, _, copied, _) => copied.push(node),
+            Owning::Object(_, _, copied, _, key) => {
+                if let Some((name, key)) = key.take() {
+                    copied.push(Member {
+                        name,
+                        key,
+                        value: node,
+                    });
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> SyntaxNode<'static> {
+        match self {
+            Owning::List(before, _, elements, before_close) => SyntaxNode {
+                before,
+                kind: SyntaxKind::List {
+                    elements: Elements(elements),
+                    before_close,
+                },
+            },
+            Owning::Object(before, _, members, before_close, _) => SyntaxNode {
+                before,
+                kind: SyntaxKind::Object {
+                    members: Members(members),
+                    before_close,
+                },
+            },
+        }
+    }
+}
+
+fn owned(text: Cow<str>) -> Cow<'static, str> {
+    Cow::Owned(text.into_owned())
+}
+
+impl fmt::Display for SyntaxTree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.root.before)?;
+        self.root.write_value(f)?;
+        f.write_str(&self.trailing)
+    }
+}
+
+/// Writes the value as it appears in the document, without what comes before
+/// it.
+impl fmt::Display for SyntaxNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_value(f)
+    }
+}
+
+//a list or object whose children are still being read
+enum Open<'a> {
+    List(Cow<'a, str>, Vec<SyntaxNode<'a>>),
+    Object(
+        Cow<'a, str>,
+        Vec<Member<'a>>,
+        //the key of the member whose value comes next
+        Option<(Cow<'a, str>, SyntaxNode<'a>)>,
+    ),
+}
+
+//assembles the events of `source` into a tree, the text between two events
+//going to the node of the second
+pub(crate) fn build<'a>(
+    source: &'a str,
+    events: impl Iterator<Item = Result<(Event<'a>, Span), ParserErrors>>,
+) -> Result<SyntaxTree<'a>, ParserErrors> {
+    let mut stack: Vec<Open<'a>> = Vec::new();
+    let mut root: Option<SyntaxNode<'a>> = None;
+    let mut last_end = 0;
+    for event in events {
+        let (event, span) = event?;
+        let before = Cow::Borrowed(&source[last_end..span.start.offset]);
+        let text = Cow::Borrowed(&source[span.start.offset..span.end.offset]);
+        last_end = span.end.offset;
+
+        let node = match event {
+            Event::StartObject => {
+                stack.push(Open::Object(before, Vec::new(), None));
+                continue;
+            }
+            Event::StartArray => {
+                stack.push(Open::List(before, Vec::new()));
+                continue;
+            }
+            Event::Key(name) => {
+                if let Some(Open::Object(_, _, key)) = stack.last_mut() {
+                    let node = SyntaxNode {
+                        before,
+                        kind: SyntaxKind::Scalar(text),
+                    };
+                    *key = Some((name, node));
+                }
+                continue;
+            }
+            Event::EndObject | Event::EndArray => match stack.pop() {
+                Some(Open::Object(open_before, members, _)) => SyntaxNode {
+                    before: open_before,
+                    kind: SyntaxKind::Object {
+                        members: Members(members),
+                        before_close: before,
+                    },
+                },
+                Some(Open::List(open_before, elements)) => SyntaxNode {
+                    before: open_before,
+                    kind: SyntaxKind::List {
+                        elements: Elements(elements),
+                        before_close: before,
+                    },
+                },
+                None => unreachable!("the parser only ends what it started"),
+            },
+            Event::String(_) | Event::Number(_) | Event::Bool(_) | Event::Null => SyntaxNode {
+                before,
+                kind: SyntaxKind::Scalar(text),
+            },
+        };
+        match stack.last_mut() {
+            None => root = Some(node),
+            Some(Open::List(_, elements)) => elements.push(node),
+            Some(Open::Object(_, members, key)) => {
+                if let Some((name, key)) = key.take() {
+                    members.push(Member {
+                        name,
+                        key,
+                        value: node,
+                    });
+                }
+            }
+        }
+    }
+    let Some(root) = root else {
+        return Err(ParserErrors::ParsingError {
+            message: "empty document".to_string(),
+            position: Default::default(),
+        });
+    };
+    Ok(SyntaxTree {
+        root,
+        trailing: Cow::Borrowed(&source[last_end..]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Number;
+
+    #[test]
+    fn test_cst_writes_back_the_input() {
+        for input in [
+            "{}",
+            " [ ] \n",
+            "\t{\"a\" :1 ,\"b\":[ true,null , -0.5E+3 ],\r\n \"c\\u0041\": \"\\n\" }\n\n",
+            "\"just a string\"",
+            "[[[],{}], {\"\": {\"x\": [1e40]}}]",
+        ] {
+            let tree = JSONDocument::default().parse_cst(input).unwrap();
+            assert_eq!(tree.to_string(), input);
+        }
+        assert!(JSONDocument::default().parse_cst("// not json\n").is_err());
+        assert!(JSONDocument::default().parse_cst("  ").is_err());
+    }
+
+    #[test]
+    fn test_cst_edits_leave_the_rest_alone() {
+        let input = "{\n  \"name\": \"app\",\n  \"ports\": [ 80,443 ],\n  \"debug\" : false\n}\n";
+        let mut tree = JSONDocument::default().parse_cst(input).unwrap();
+
+        let ports = tree.root_mut().get_mut("ports").unwrap();
+        ports.get_index_mut(1).unwrap().replace("8443").unwrap();
+        tree.root_mut()
+            .get_mut("debug")
+            .unwrap()
+            .replace("{\"level\": 2}")
+            .unwrap();
+
+        assert_eq!(
+            tree.to_string(),
+            "{\n  \"name\": \"app\",\n  \"ports\": [ 80,8443 ],\n  \"debug\" : {\"level\": 2}\n}\n"
+        );
+        assert_eq!(
+            tree.root()
+                .get("debug")
+                .unwrap()
+                .get("level")
+                .unwrap()
+                .to_string(),
+            "2"
+        );
+    }
+
+    #[test]
+    fn test_cst_deep_tree() {
+        let input = format!("{}1{}", "[ {\"a\":".repeat(50_000), "} ]".repeat(50_000));
+        let json_document = JSONDocument::new(crate::ParserOptions {
+            max_depth: None,
+            ..Default::default()
+        });
+        let tree = json_document.parse_cst(&input).unwrap();
+        assert!(tree.root().get_index(0).unwrap().get("a").is_some());
+        drop(tree);
+        let tree = json_document.parse_cst(&input).unwrap().into_owned();
+        assert_eq!(tree.to_string(), input);
+    }
+
+    #[test]
+    fn test_cst_lookup_and_values() {
+        let input = r#"{"caf\u00e9": [1, "two"], "n": 1.50}"#;
+        let mut tree = JSONDocument::default().parse_cst(input).unwrap();
+
+        let list = tree.root().get("café").unwrap();
+        assert_eq!(list.to_string(), r#"[1, "two"]"#);
+        assert_eq!(
+            list.get_index(1).unwrap().to_value().unwrap(),
+            JsonValue::String("two".into())
+        );
+        //numbers keep their spelling
+        assert_eq!(tree.root().get("n").unwrap().to_string(), "1.50");
+        assert_eq!(
+            tree.root().get("n").unwrap().to_value().unwrap(),
+            JsonValue::Number(Number::Float(1.5))
+        );
+        assert!(tree.root().get_index(0).is_none());
+
+        let node = tree.root_mut().get_mut("n").unwrap();
+        assert!(node.replace("[1,").is_err());
+        assert_eq!(node.to_string(), "1.50");
+    }
+}
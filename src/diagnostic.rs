@@ -0,0 +1,245 @@
+use std::io::BufRead;
+
+use crate::ParserErrors;
+
+//lines shown before and after the offending one
+const CONTEXT_LINES: usize = 2;
+//longer lines (minified files) only show this many chars around the column
+const MAX_LINE_WIDTH: usize = 120;
+const TAB_WIDTH: usize = 4;
+
+//ANSI styles used when colors are on
+const ERROR_STYLE: &str = "1;31";
+const GUTTER_STYLE: &str = "1;34";
+const BOLD: &str = "1";
+
+//renders the error like rustc does:
+//
+//error: invalid json
+// --> data.json:2:8
+//  |
+//1 | {
+//2 |   "key", 1
+//  |        ^ expected ':' after object key
+//3 | }
+//  |
+//
+//only the lines shown are kept, the rest of the source is read past. With
+//`color` the parts are styled the way rustc does it in a terminal
+pub(crate) fn render(
+    error: &ParserErrors,
+    source: impl BufRead,
+    path: &str,
+    color: bool,
+) -> String {
+    let paint = |text: &str, style: &str| -> String {
+        if color {
+            format!("\x1b[{style}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    };
+    let Some(position) = error.position() else {
+        return format!(
+            "{}{}",
+            paint("error", ERROR_STYLE),
+            paint(&format!(": {error}"), BOLD)
+        );
+    };
+
+    let first = position.line.saturating_sub(CONTEXT_LINES).max(1);
+    let lines: Vec<String> = source
+        .split(b'\n')
+        .map_while(Result::ok)
+        .skip(first - 1)
+        .take(position.line + CONTEXT_LINES + 1 - first)
+        .map(|line| {
+            let line = String::from_utf8_lossy(&line).into_owned();
+            match line.strip_suffix('\r') {
+                Some(line) => line.to_string(),
+                None => line,
+            }
+        })
+        .collect();
+    let last = (first + lines.len()).saturating_sub(1).max(position.line);
+    let gutter = " ".repeat(last.to_string().len());
+    let bar = paint("|", GUTTER_STYLE);
+
+    let mut output = format!(
+        "{}{}\n{gutter}{} {path}:{}:{}\n{gutter} {bar}\n",
+        paint("error", ERROR_STYLE),
+        paint(&format!(": {}", headline(error)), BOLD),
+        paint("-->", GUTTER_STYLE),
+        position.line,
+        position.column
+    );
+    for number in first..=last {
+        let line = lines
+            .get(number - first)
+            .map(String::as_str)
+            .unwrap_or_default();
+        let number_column = paint(
+            &format!("{number:>width$} |", width = gutter.len()),
+            GUTTER_STYLE,
+        );
+        if number != position.line {
+            let (text, _) = window(line, 1);
+            output.push_str(&format!("{number_column} {text}\n"));
+            continue;
+        }
+        let (text, caret) = window(line, position.column);
+        output.push_str(&format!("{number_column} {text}\n"));
+        output.push_str(&format!(
+            "{gutter} {bar} {}{}\n",
+            " ".repeat(caret),
+            paint(&format!("^ {}", label(error)), ERROR_STYLE)
+        ));
+    }
+    output.push_str(&format!("{gutter} {bar}"));
+    output
+}
+
+pub(crate) fn headline(error: &ParserErrors) -> &'static str {
+    match error {
+        ParserErrors::TokenizeError(_) => "not able to tokenize",
+        ParserErrors::ParsingError { .. } => "invalid json",
+        ParserErrors::InvalidEscape { .. }
+        | ParserErrors::ControlCharacter { .. }
+        | ParserErrors::UnterminatedString { .. } => "invalid string",
+        ParserErrors::InvalidNumber { .. } => "invalid number",
+        ParserErrors::DepthLimitExceeded { .. } => "document nested too deeply",
+        ParserErrors::InvalidUtf8 { .. } => "invalid UTF-8",
+        _ => "error",
+    }
+}
+
+pub(crate) fn label(error: &ParserErrors) -> String {
+    match error {
+        ParserErrors::TokenizeError(_) => "unexpected character".to_string(),
+        ParserErrors::ParsingError { message, .. } => message.clone(),
+        ParserErrors::InvalidEscape { sequence, .. } => {
+            format!("invalid escape sequence `{sequence}`")
+        }
+        ParserErrors::ControlCharacter { character, .. } => {
+            format!("control character {character:?} must be escaped")
+        }
+        ParserErrors::UnterminatedString { .. } => "string is never closed".to_string(),
+        ParserErrors::InvalidNumber { reason, .. } => reason.to_string(),
+        ParserErrors::DepthLimitExceeded { depth, .. } => {
+            format!("this opens nesting level {depth}")
+        }
+        ParserErrors::InvalidUtf8 { .. } => "invalid byte sequence".to_string(),
+        _ => error.to_string(),
+    }
+}
+
+//the printable part of a line plus how many columns the caret for `column`
+//has to be indented, tabs expanded so the caret lines up
+fn window(line: &str, column: usize) -> (String, usize) {
+    let chars: Vec<char> = line.chars().collect();
+    let column = column.saturating_sub(1).min(chars.len());
+    let (start, prefix) = if chars.len() > MAX_LINE_WIDTH && column > MAX_LINE_WIDTH / 2 {
+        (column - MAX_LINE_WIDTH / 2, "...")
+    } else {
+        (0, "")
+    };
+    let end = (start + MAX_LINE_WIDTH).min(chars.len());
+    let suffix = if end < chars.len() { "..." } else { "" };
+
+    let expand = |chars: &[char]| -> String {
+        chars
+            .iter()
+            .map(|c| match c {
+                '\t' => " ".repeat(TAB_WIDTH),
+                _ => c.to_string(),
+            })
+            .collect()
+    };
+    let text = format!("{prefix}{}{suffix}", expand(&chars[start..end]));
+    let caret = prefix.len() + expand(&chars[start..column]).chars().count();
+    (text, caret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    #[test]
+    fn test_render_points_at_column() {
+        let source = "{\n  \"key\", 1\n}\n";
+        let error = from_str(source).unwrap_err();
+
+        assert_eq!(
+            render(&error, source.as_bytes(), "data.json", false),
+            "error: invalid json\n \
+             --> data.json:2:8\n  \
+             |\n\
+             1 | {\n\
+             2 |   \"key\", 1\n  \
+             |        ^ expected ':' after object key\n\
+             3 | }\n  \
+             |"
+        );
+    }
+
+    #[test]
+    fn test_render_shortens_long_lines() {
+        let source = format!("[{}?{}]", "1,".repeat(200), ",1".repeat(200));
+        let error = from_str(&source).unwrap_err();
+        let rendered = render(&error, source.as_bytes(), "long.json", false);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert!(lines[3].starts_with("1 | ...1,1,"));
+        assert!(lines[3].ends_with("..."));
+        //the caret still sits under the question mark
+        let caret = lines[4].find('^').unwrap();
+        assert_eq!(&lines[3][caret..caret + 1], "?");
+    }
+
+    #[test]
+    fn test_render_without_position() {
+        let error = ParserErrors::ArgumentError("please provide a filename".into());
+        assert_eq!(
+            render(&error, "".as_bytes(), "-", false),
+            "error: please provide a filename"
+        );
+    }
+
+    #[test]
+    fn test_render_only_reads_lines_around_the_error() {
+        let mut source = "[\n".to_string();
+        for _ in 0..50 {
+            source.push_str("  1,\r\n");
+        }
+        source.push_str("  ?\n]");
+        let error = from_str(&source).unwrap_err();
+        let rendered = render(&error, source.as_bytes(), "big.json", false);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(lines[1], "  --> big.json:52:3");
+        assert_eq!(lines[3], "50 |   1,");
+        assert_eq!(lines[5], "52 |   ?");
+        assert_eq!(lines[7], "53 | ]");
+        assert_eq!(lines.len(), 9);
+    }
+
+    #[test]
+    fn test_render_with_color() {
+        let source = "[1,]";
+        let error = from_str(source).unwrap_err();
+        let rendered = render(&error, source.as_bytes(), "data.json", true);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "\x1b[1;31merror\x1b[0m\x1b[1m: invalid json\x1b[0m"
+        );
+        assert_eq!(lines[1], " \x1b[1;34m-->\x1b[0m data.json:1:4");
+        assert_eq!(lines[3], "\x1b[1;34m1 |\x1b[0m [1,]");
+        assert_eq!(
+            lines[4],
+            "  \x1b[1;34m|\x1b[0m    \x1b[1;31m^ trailing comma before ']'\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+//whether the argument is a pattern to expand rather than a path
+pub(crate) fn is_glob(arg: &str) -> bool {
+    arg.contains(['*', '?'])
+}
+
+//the paths matching `pattern`, sorted. In a path component `*` stands for any
+//run of characters and `?` for a single one, and a component that is just
+//`**` stands for any number of directories. Like in a shell, wildcards only
+//match names starting with a dot when the pattern does too. Directories that
+//can't be listed go to `unreadable` instead
+pub(crate) fn expand_glob(pattern: &str, unreadable: &mut Vec<Unreadable>) -> Vec<PathBuf> {
+    let mut matched = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let Component::Normal(part) = component else {
+            for path in &mut matched {
+                path.push(component);
+            }
+            continue;
+        };
+        let part = part.to_string_lossy();
+        if !is_glob(&part) {
+            for path in &mut matched {
+                path.push(&*part);
+            }
+            continue;
+        }
+        let mut next = Vec::new();
+        for path in &matched {
+            //files matched so far can't have anything below them
+            if !path.as_os_str().is_empty() && !path.is_dir() {
+                continue;
+            }
+            if part == "**" {
+                next.push(path.clone());
+                next.extend(walk(path, |_, file_type| file_type.is_dir(), unreadable));
+                continue;
+            }
+            let names = match list(path) {
+                Ok(names) => names,
+                Err(error) => {
+                    unreadable.push((path.clone(), error));
+                    continue;
+                }
+            };
+            for (name, _) in names {
+                if name_matches(&part, &name) {
+                    next.push(path.join(name));
+                }
+            }
+        }
+        matched = next;
+    }
+    matched.retain(|path| path.exists());
+    matched.sort();
+    matched.dedup();
+    matched
+}
+
+//every `.json` file below `dir`, sorted, leaving out hidden files and
+//directories. Directories that can't be listed go to `unreadable`
+pub(crate) fn json_files(dir: &Path, unreadable: &mut Vec<Unreadable>) -> Vec<PathBuf> {
+    let mut files = walk(
+        dir,
+        |path, _| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension == "json")
+        },
+        unreadable,
+    );
+    files.sort();
+    files
+}
+
+//a directory that couldn't be listed and why
+pub(crate) type Unreadable = (PathBuf, io::Error);
+
+//the paths below `dir` that `keep` accepts, not following hidden directories.
+//Symbolic links to directories aren't followed either, so a link back up the
+//tree can't send the walk around in circles
+fn walk(
+    dir: &Path,
+    keep: impl Fn(&Path, fs::FileType) -> bool,
+    unreadable: &mut Vec<Unreadable>,
+) -> Vec<PathBuf> {
+    //an explicit stack, like everywhere else, instead of recursing
+    let mut stack = vec![dir.to_path_buf()];
+    let mut found = Vec::new();
+    while let Some(dir) = stack.pop() {
+        let names = match list(&dir) {
+            Ok(names) => names,
+            Err(error) => {
+                unreadable.push((dir, error));
+                continue;
+            }
+        };
+        for (name, file_type) in names {
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = dir.join(name);
+            if keep(&path, file_type) {
+                found.push(path.clone());
+            }
+            if file_type.is_dir() {
+                stack.push(path);
+            }
+        }
+    }
+    found
+}
+
+//the names in a directory with their types, which don't follow symbolic
+//links. An empty path is the current directory
+fn list(dir: &Path) -> io::Result<Vec<(String, fs::FileType)>> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        names.push((name, entry.file_type()?));
+    }
+    Ok(names)
+}
+
+fn name_matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    //walks both, remembering the last `*` so it can take one more character
+    //when what follows it stops matching
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some('?') => (p, n) = (p + 1, n + 1),
+            Some(c) if *c == name[n] => (p, n) = (p + 1, n + 1),
+            _ => {
+                let Some((star_p, star_n)) = star else {
+                    return false;
+                };
+                star = Some((star_p, star_n + 1));
+                (p, n) = (star_p + 1, star_n + 1);
+            }
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::ops::Deref;
+
+    //a fresh directory under the system temp dir, deleted with everything in
+    //it when dropped so a failing test doesn't leave it behind
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(name: &str) -> TempDir {
+            let path =
+                std::env::temp_dir().join(format!("json_parser_{name}_{}", std::process::id()));
+            //left over from a run that was killed
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_name_matches() {
+        assert!(name_matches("*.json", "data.json"));
+        assert!(!name_matches("*.json", ".json.json"));
+        assert!(name_matches(".*", ".hidden"));
+        assert!(name_matches("pass?.json", "pass1.json"));
+        assert!(!name_matches("pass?.json", "pass10.json"));
+        assert!(name_matches("*a*b*", "xxaxxbxx"));
+        assert!(!name_matches("*a*b", "xxaxxbxx"));
+        assert!(name_matches("*", "anything"));
+        assert!(!name_matches("*.json", "data.jsonl"));
+    }
+
+    #[test]
+    fn test_expand_glob_and_json_files() {
+        let root = TempDir::new("files");
+        for dir in ["a/b", "a/.git", "c"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "one.json",
+            "a/two.json",
+            "a/b/three.json",
+            "a/.git/four.json",
+            "c/notes.txt",
+        ] {
+            fs::write(root.join(file), "[]").unwrap();
+        }
+        let relative = |paths: Vec<PathBuf>| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| path.strip_prefix(&root).unwrap().display().to_string())
+                .collect()
+        };
+
+        let mut unreadable = Vec::new();
+        let pattern = root.join("*").join("*.json");
+        assert_eq!(
+            relative(expand_glob(pattern.to_str().unwrap(), &mut unreadable)),
+            ["a/two.json"]
+        );
+        let pattern = root.join("**").join("*.json");
+        assert_eq!(
+            relative(expand_glob(pattern.to_str().unwrap(), &mut unreadable)),
+            ["a/b/three.json", "a/two.json", "one.json"]
+        );
+        let pattern = root.join("*.yaml");
+        assert!(expand_glob(pattern.to_str().unwrap(), &mut unreadable).is_empty());
+
+        assert_eq!(
+            relative(json_files(&root, &mut unreadable)),
+            ["a/b/three.json", "a/two.json", "one.json"]
+        );
+        assert!(unreadable.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_skips_linked_directories() {
+        let root = TempDir::new("links");
+        fs::create_dir_all(root.join("d")).unwrap();
+        fs::write(root.join("d/one.json"), "[]").unwrap();
+        std::os::unix::fs::symlink("..", root.join("d/loop")).unwrap();
+        std::os::unix::fs::symlink("one.json", root.join("d/linked.json")).unwrap();
+
+        let mut unreadable = Vec::new();
+        let files: Vec<PathBuf> = json_files(&root, &mut unreadable)
+            .iter()
+            .map(|path| path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect();
+        //linked files still count
+        assert_eq!(files, [Path::new("d/linked.json"), Path::new("d/one.json")]);
+        let pattern = root.join("**").join("*.json");
+        assert_eq!(
+            expand_glob(pattern.to_str().unwrap(), &mut unreadable).len(),
+            2
+        );
+
+        let missing = root.join("missing");
+        assert!(json_files(&missing, &mut unreadable).is_empty());
+        assert_eq!(unreadable.len(), 1);
+        assert_eq!(unreadable[0].0, missing);
+    }
+}
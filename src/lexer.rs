@@ -0,0 +1,918 @@
+use std::{borrow::Cow, collections::VecDeque, io::BufRead};
+
+use crate::position::Chars;
+use crate::{ParserErrors, Position, Span};
+
+/// The kinds of token the [`Lexer`] produces.
+///
+/// A string is read as its opening quote, its decoded contents and its closing
+/// quote. The last token of every input is [`Tokens::EOF`].
+///
+/// Strings without escapes borrow from the input when it is a `&str`, see
+/// [`Lexer::for_str`].
+#[derive(Debug, PartialEq, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Tokens<'a> {
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    DoubleQuote,
+    Colon,
+    Comma,
+    StringValue(Cow<'a, str>),
+    BooleanValue(bool),
+    /// A number exactly as written, already checked against the grammar.
+    NumberValue(String),
+    NullValue,
+    EOF,
+}
+
+/// A token together with where it was found in the input.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token<'a> {
+    pub kind: Tokens<'a>,
+    pub span: Span,
+}
+impl<'a> Token<'a> {
+    pub(crate) fn new(kind: Tokens<'a>, start: Position, end: Position) -> Token<'a> {
+        Token {
+            kind,
+            span: Span::new(start, end),
+        }
+    }
+}
+
+/// Reads tokens one at a time from a buffered reader.
+///
+/// Bytes are pulled from the reader as the tokens are asked for, so memory use
+/// depends on the longest string or number and not on the size of the input.
+/// The iterator ends after the [`Tokens::EOF`] token or the first error.
+///
+/// ```
+/// use json_parser::{Lexer, Tokens};
+///
+/// let kinds: Vec<Tokens> = Lexer::new("[true]".as_bytes())
+///     .map(|token| token.unwrap().kind)
+///     .collect();
+/// assert_eq!(
+///     kinds,
+///     vec![
+///         Tokens::LeftBracket,
+///         Tokens::BooleanValue(true),
+///         Tokens::RightBracket,
+///         Tokens::EOF
+///     ]
+/// );
+/// ```
+pub struct Lexer<'a, R> {
+    chars: Chars<R>,
+    //the whole input when it is in memory, so strings can be sliced from it
+    input: Option<&'a str>,
+    //a string is lexed whole, its contents and closing quote wait here
+    pending: VecDeque<Token<'a>>,
+    strip_bom: bool,
+    finished: bool,
+}
+
+impl<R: BufRead> Lexer<'static, R> {
+    pub fn new(reader: R) -> Lexer<'static, R> {
+        Lexer {
+            chars: Chars::new(reader),
+            input: None,
+            pending: VecDeque::new(),
+            strip_bom: false,
+            finished: false,
+        }
+    }
+}
+
+impl<'a> Lexer<'a, &'a [u8]> {
+    /// Reads tokens from a string, handing out strings without escapes as
+    /// slices of `input` instead of copying them.
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// use json_parser::{Lexer, Tokens};
+    ///
+    /// let kinds: Vec<Tokens> = Lexer::for_str(r#"["plain", "esc\naped"]"#)
+    ///     .map(|token| token.unwrap().kind)
+    ///     .collect();
+    /// assert!(matches!(kinds[2], Tokens::StringValue(Cow::Borrowed("plain"))));
+    /// assert!(matches!(kinds[6], Tokens::StringValue(Cow::Owned(_))));
+    /// ```
+    pub fn for_str(input: &'a str) -> Lexer<'a, &'a [u8]> {
+        Lexer {
+            chars: Chars::new(input.as_bytes()),
+            input: Some(input),
+            pending: VecDeque::new(),
+            strip_bom: false,
+            finished: false,
+        }
+    }
+}
+
+impl<'a, R: BufRead> Lexer<'a, R> {
+    /// Skips a byte order mark at the start of the input instead of failing
+    /// on it.
+    pub fn strip_bom(mut self, strip: bool) -> Self {
+        self.strip_bom = strip;
+        self
+    }
+
+    fn next_token(&mut self) -> Result<Token<'a>, ParserErrors> {
+        if let Some(token) = self.pending.pop_front() {
+            return Ok(token);
+        }
+        let chars = &mut self.chars;
+        loop {
+            let Some((start, c)) = chars.next()? else {
+                return Ok(Token::new(Tokens::EOF, chars.position(), chars.position()));
+            };
+            //eat the whitespace nom nom nom
+            if matches!(c, ' ' | '\t' | '\n' | '\r') {
+                continue;
+            }
+            if c == '\u{feff}' && start.offset == 0 {
+                if self.strip_bom {
+                    continue;
+                }
+                return Err(ParserErrors::ParsingError {
+                    message: "unexpected byte order mark".to_string(),
+                    position: start,
+                });
+            }
+
+            let kind = match c {
+                '{' => Tokens::LeftBrace,
+                '}' => Tokens::RightBrace,
+                '[' => Tokens::LeftBracket,
+                ']' => Tokens::RightBracket,
+                ':' => Tokens::Colon,
+                ',' => Tokens::Comma,
+                '"' => {
+                    let open = Token::new(Tokens::DoubleQuote, start, chars.position());
+                    let buffer_start = chars.position();
+                    let (buffer, close) = lex_string(chars, start, self.input)?;
+                    self.pending.push_back(Token::new(
+                        Tokens::StringValue(buffer),
+                        buffer_start,
+                        close,
+                    ));
+                    self.pending.push_back(Token::new(
+                        Tokens::DoubleQuote,
+                        close,
+                        chars.position(),
+                    ));
+                    return Ok(open);
+                }
+                '-' | '0'..='9' => Tokens::NumberValue(lex_number(chars, c, start)?),
+                _ => {
+                    if !c.is_alphabetic() {
+                        return Err(ParserErrors::TokenizeError(start));
+                    }
+                    let mut buffer: String = c.to_string();
+                    take_word(chars, &mut buffer)?;
+                    match buffer.as_str() {
+                        "true" => Tokens::BooleanValue(true),
+                        "false" => Tokens::BooleanValue(false),
+                        "null" => Tokens::NullValue,
+                        _ => return Err(ParserErrors::TokenizeError(start)),
+                    }
+                }
+            };
+            return Ok(Token::new(kind, start, chars.position()));
+        }
+    }
+}
+
+impl<'a, R: BufRead> Iterator for Lexer<'a, R> {
+    type Item = Result<Token<'a>, ParserErrors>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let token = self.next_token();
+        self.finished = matches!(
+            token,
+            Err(_)
+                | Ok(Token {
+                    kind: Tokens::EOF,
+                    ..
+                })
+        );
+        Some(token)
+    }
+}
+
+//reads the rest of a string after its opening quote at `start`, returning the
+//decoded contents and where the closing quote is. With the whole `input` at
+//hand the contents are only copied once an escape has to be decoded
+fn lex_string<'a, R: BufRead>(
+    chars: &mut Chars<R>,
+    start: Position,
+    input: Option<&'a str>,
+) -> Result<(Cow<'a, str>, Position), ParserErrors> {
+    let contents_start = chars.position();
+    let mut buffer: String = String::new();
+    let mut copying = input.is_none();
+    loop {
+        let Some((char_start, c)) = chars.next()? else {
+            return Err(ParserErrors::UnterminatedString { position: start });
+        };
+        //don't eat the whitespace inside quotes
+        match c {
+            '"' => {
+                let contents = match input {
+                    Some(input) if !copying => {
+                        Cow::Borrowed(&input[contents_start.offset..char_start.offset])
+                    }
+                    _ => Cow::Owned(buffer),
+                };
+                return Ok((contents, char_start));
+            }
+            '\\' => {
+                if let (Some(input), false) = (input, copying) {
+                    buffer.push_str(&input[contents_start.offset..char_start.offset]);
+                    copying = true;
+                }
+                buffer.push(parse_escape(chars, char_start)?);
+            }
+            //tabs, newlines and the like have to be escaped
+            '\u{0}'..='\u{1f}' => {
+                return Err(ParserErrors::ControlCharacter {
+                    character: c,
+                    position: char_start,
+                });
+            }
+            _ if copying => buffer.push(c),
+            _ => (),
+        }
+    }
+}
+
+//reads a number following the RFC 8259 grammar and returns it as written:
+//an optional minus, no leading zeros, digits after the dot and the exponent
+fn lex_number<R: BufRead>(
+    chars: &mut Chars<R>,
+    first: char,
+    start: Position,
+) -> Result<String, ParserErrors> {
+    let mut number = first.to_string();
+    let invalid = |chars: &mut Chars<R>, mut number: String, reason: &'static str| {
+        //report everything that was glued to the number
+        if let Err(error) = take_word(chars, &mut number) {
+            return error;
+        }
+        ParserErrors::InvalidNumber {
+            number,
+            reason,
+            position: start,
+        }
+    };
+
+    let first_digit = if first == '-' {
+        match chars.peek()? {
+            Some(c) if c.is_ascii_digit() => {
+                chars.next()?;
+                number.push(c);
+                c
+            }
+            _ => return Err(invalid(chars, number, "expected a digit after '-'")),
+        }
+    } else {
+        first
+    };
+    if first_digit == '0' {
+        if chars.peek()?.is_some_and(|c| c.is_ascii_digit()) {
+            return Err(invalid(chars, number, "leading zeros are not allowed"));
+        }
+    } else {
+        take_digits(chars, &mut number)?;
+    }
+
+    if chars.peek()? == Some('.') {
+        chars.next()?;
+        number.push('.');
+        if take_digits(chars, &mut number)? == 0 {
+            return Err(invalid(chars, number, "expected digits after '.'"));
+        }
+    }
+
+    if let Some(e @ ('e' | 'E')) = chars.peek()? {
+        chars.next()?;
+        number.push(e);
+        if let Some(sign @ ('+' | '-')) = chars.peek()? {
+            chars.next()?;
+            number.push(sign);
+        }
+        if take_digits(chars, &mut number)? == 0 {
+            return Err(invalid(chars, number, "expected digits in the exponent"));
+        }
+    }
+
+    if chars
+        .peek()?
+        .is_some_and(|c| c.is_alphanumeric() || c == '.' || c == '+' || c == '-')
+    {
+        return Err(invalid(chars, number, "unexpected character in number"));
+    }
+    Ok(number)
+}
+
+//moves ascii digits into the buffer, returning how many there were
+fn take_digits<R: BufRead>(
+    chars: &mut Chars<R>,
+    buffer: &mut String,
+) -> Result<usize, ParserErrors> {
+    let mut count = 0;
+    while let Some(c) = chars.peek()?.filter(|c| c.is_ascii_digit()) {
+        chars.next()?;
+        buffer.push(c);
+        count += 1;
+    }
+    Ok(count)
+}
+
+//moves the rest of a word (letters, digits and number signs) into the buffer
+fn take_word<R: BufRead>(chars: &mut Chars<R>, buffer: &mut String) -> Result<(), ParserErrors> {
+    while let Some(c) = chars
+        .peek()?
+        .filter(|c| c.is_alphanumeric() || *c == '.' || *c == '+' || *c == '-')
+    {
+        chars.next()?;
+        buffer.push(c);
+    }
+    Ok(())
+}
+
+//decodes the escape sequence after a backslash, `start` being where the
+//backslash is
+fn parse_escape<R: BufRead>(chars: &mut Chars<R>, start: Position) -> Result<char, ParserErrors> {
+    let Some((_, c)) = chars.next()? else {
+        return Err(invalid_escape("\\", start));
+    };
+    let decoded = match c {
+        '"' => '"',
+        '\\' => '\\',
+        '/' => '/',
+        'b' => '\u{8}',
+        'f' => '\u{c}',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'u' => {
+            let high = parse_hex_escape(chars, start)?;
+            let code_point = match high {
+                0xD800..=0xDBFF => {
+                    //a high surrogate has to be followed by an escaped low one
+                    let low_start = chars.position();
+                    let (Some((_, '\\')), Some((_, 'u'))) = (chars.next()?, chars.next()?) else {
+                        return Err(invalid_escape(&format!("\\u{high:04X}"), start));
+                    };
+                    let low = parse_hex_escape(chars, low_start)?;
+                    if !(0xDC00..=0xDFFF).contains(&low) {
+                        return Err(invalid_escape(&format!("\\u{low:04X}"), low_start));
+                    }
+                    0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                }
+                0xDC00..=0xDFFF => {
+                    return Err(invalid_escape(&format!("\\u{high:04X}"), start));
+                }
+                _ => high,
+            };
+            let Some(decoded) = char::from_u32(code_point) else {
+                return Err(invalid_escape(&format!("\\u{high:04X}"), start));
+            };
+            decoded
+        }
+        _ => return Err(invalid_escape(&format!("\\{c}"), start)),
+    };
+    Ok(decoded)
+}
+
+//reads the four hex digits of a \uXXXX escape
+fn parse_hex_escape<R: BufRead>(
+    chars: &mut Chars<R>,
+    start: Position,
+) -> Result<u32, ParserErrors> {
+    let mut digits = String::new();
+    let mut code_point: u32 = 0;
+    for _ in 0..4 {
+        let Some((_, c)) = chars.next()? else {
+            return Err(invalid_escape(&format!("\\u{digits}"), start));
+        };
+        digits.push(c);
+        let Some(digit) = c.to_digit(16) else {
+            return Err(invalid_escape(&format!("\\u{digits}"), start));
+        };
+        code_point = code_point * 16 + digit;
+    }
+    Ok(code_point)
+}
+
+fn invalid_escape(sequence: &str, position: Position) -> ParserErrors {
+    ParserErrors::InvalidEscape {
+        sequence: sequence.to_string(),
+        position,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, BufReader, Read};
+
+    fn tokenize(input: &str) -> Result<Vec<Token<'_>>, ParserErrors> {
+        Lexer::for_str(input).collect()
+    }
+
+    //checks tokenizing `input` fails at byte `offset` with an error `expected`
+    //accepts
+    #[track_caller]
+    fn assert_tokenize_error(
+        input: impl AsRef<[u8]>,
+        offset: usize,
+        expected: impl FnOnce(&ParserErrors) -> bool,
+    ) {
+        let input = input.as_ref();
+        let text = String::from_utf8_lossy(input);
+        match Lexer::new(input).collect::<Result<Vec<Token>, _>>() {
+            Err(error) => {
+                assert!(expected(&error), "{text:?} gave {error:?}");
+                assert_eq!(error.position().unwrap().offset, offset, "{text:?}");
+            }
+            other => panic!("{text:?} gave {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_tokenize_on_braces() {
+        let tokens = tokenize("{}").unwrap();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(Tokens::LeftBrace, tokens[0].kind);
+        assert_eq!(Tokens::RightBrace, tokens[1].kind);
+    }
+    #[test]
+    fn test_tokenize_string_values() {
+        //{"key": "value"}
+        let tokens = tokenize("{\"key\": \"value\"}").unwrap();
+        assert_eq!(tokens.len(), 10);
+        assert_eq!(Tokens::LeftBrace, tokens[0].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[1].kind);
+        assert_eq!(Tokens::StringValue("key".into()), tokens[2].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[3].kind);
+        assert_eq!(Tokens::Colon, tokens[4].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[5].kind);
+        assert_eq!(Tokens::StringValue("value".into()), tokens[6].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[7].kind);
+        assert_eq!(Tokens::RightBrace, tokens[8].kind);
+    }
+
+    #[test]
+    fn test_tokenize_errors_on_unknown() {
+        assert!(tokenize("?").is_err());
+    }
+
+    #[test]
+    fn test_tokenize_bool_values() {
+        let tokens = tokenize("{\"key\": true, \"key2\": false}").unwrap();
+        assert_eq!(tokens.len(), 14);
+        assert_eq!(Tokens::LeftBrace, tokens[0].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[1].kind);
+        assert_eq!(Tokens::StringValue("key".into()), tokens[2].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[3].kind);
+        assert_eq!(Tokens::Colon, tokens[4].kind);
+        assert_eq!(Tokens::BooleanValue(true), tokens[5].kind);
+        assert_eq!(Tokens::Comma, tokens[6].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[7].kind);
+        assert_eq!(Tokens::StringValue("key2".into()), tokens[8].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[9].kind);
+        assert_eq!(Tokens::Colon, tokens[10].kind);
+        assert_eq!(Tokens::BooleanValue(false), tokens[11].kind);
+        assert_eq!(Tokens::RightBrace, tokens[12].kind);
+    }
+
+    #[test]
+    fn test_tokenize_integer_values() {
+        let tokens = tokenize("{\"key\": -32, \"key2\": 14}").unwrap();
+        assert_eq!(tokens.len(), 14);
+        assert_eq!(Tokens::LeftBrace, tokens[0].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[1].kind);
+        assert_eq!(Tokens::StringValue("key".into()), tokens[2].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[3].kind);
+        assert_eq!(Tokens::Colon, tokens[4].kind);
+        assert_eq!(Tokens::NumberValue("-32".into()), tokens[5].kind);
+        assert_eq!(Tokens::Comma, tokens[6].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[7].kind);
+        assert_eq!(Tokens::StringValue("key2".into()), tokens[8].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[9].kind);
+        assert_eq!(Tokens::Colon, tokens[10].kind);
+        assert_eq!(Tokens::NumberValue("14".into()), tokens[11].kind);
+        assert_eq!(Tokens::RightBrace, tokens[12].kind);
+    }
+
+    #[test]
+    fn test_tokenize_float_values() {
+        let tokens = tokenize("{\"key\": -3.2, \"key2\": 0.14}").unwrap();
+        assert_eq!(tokens.len(), 14);
+        assert_eq!(Tokens::LeftBrace, tokens[0].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[1].kind);
+        assert_eq!(Tokens::StringValue("key".into()), tokens[2].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[3].kind);
+        assert_eq!(Tokens::Colon, tokens[4].kind);
+        assert_eq!(Tokens::NumberValue("-3.2".into()), tokens[5].kind);
+        assert_eq!(Tokens::Comma, tokens[6].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[7].kind);
+        assert_eq!(Tokens::StringValue("key2".into()), tokens[8].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[9].kind);
+        assert_eq!(Tokens::Colon, tokens[10].kind);
+        assert_eq!(Tokens::NumberValue("0.14".into()), tokens[11].kind);
+        assert_eq!(Tokens::RightBrace, tokens[12].kind);
+    }
+
+    #[test]
+    fn test_tokenize_float_scientific_notation_values() {
+        let tokens = tokenize("{\"key\": -3E3, \"key2\": 14E-4}").unwrap();
+        assert_eq!(tokens.len(), 14);
+        assert_eq!(Tokens::LeftBrace, tokens[0].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[1].kind);
+        assert_eq!(Tokens::StringValue("key".into()), tokens[2].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[3].kind);
+        assert_eq!(Tokens::Colon, tokens[4].kind);
+        assert_eq!(Tokens::NumberValue("-3E3".into()), tokens[5].kind);
+        assert_eq!(Tokens::Comma, tokens[6].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[7].kind);
+        assert_eq!(Tokens::StringValue("key2".into()), tokens[8].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[9].kind);
+        assert_eq!(Tokens::Colon, tokens[10].kind);
+        assert_eq!(Tokens::NumberValue("14E-4".into()), tokens[11].kind);
+        assert_eq!(Tokens::RightBrace, tokens[12].kind);
+    }
+
+    #[test]
+    fn test_tokenize_null_values() {
+        let tokens = tokenize("{\"key\": null, \"key2\": null}").unwrap();
+        assert_eq!(tokens.len(), 14);
+        assert_eq!(Tokens::LeftBrace, tokens[0].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[1].kind);
+        assert_eq!(Tokens::StringValue("key".into()), tokens[2].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[3].kind);
+        assert_eq!(Tokens::Colon, tokens[4].kind);
+        assert_eq!(Tokens::NullValue, tokens[5].kind);
+        assert_eq!(Tokens::Comma, tokens[6].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[7].kind);
+        assert_eq!(Tokens::StringValue("key2".into()), tokens[8].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[9].kind);
+        assert_eq!(Tokens::Colon, tokens[10].kind);
+        assert_eq!(Tokens::NullValue, tokens[11].kind);
+        assert_eq!(Tokens::RightBrace, tokens[12].kind);
+    }
+
+    #[test]
+    fn test_tokenize_on_brackets() {
+        let tokens = tokenize("[]").unwrap();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(Tokens::LeftBracket, tokens[0].kind);
+        assert_eq!(Tokens::RightBracket, tokens[1].kind);
+    }
+
+    #[test]
+    fn test_tokenize_list() {
+        let tokens = tokenize("[\"one\", 2, true]").unwrap();
+
+        assert_eq!(tokens.len(), 10);
+        assert_eq!(Tokens::LeftBracket, tokens[0].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[1].kind);
+        assert_eq!(Tokens::StringValue("one".into()), tokens[2].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[3].kind);
+        assert_eq!(Tokens::Comma, tokens[4].kind);
+        assert_eq!(Tokens::NumberValue("2".into()), tokens[5].kind);
+        assert_eq!(Tokens::Comma, tokens[6].kind);
+        assert_eq!(Tokens::BooleanValue(true), tokens[7].kind);
+        assert_eq!(Tokens::RightBracket, tokens[8].kind);
+    }
+
+    #[test]
+    fn test_tokenize_list_nested_object() {
+        let tokens = tokenize("[\"one\", 2, { \"inner key\": true}]").unwrap();
+
+        assert_eq!(tokens.len(), 16);
+        assert_eq!(Tokens::LeftBracket, tokens[0].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[1].kind);
+        assert_eq!(Tokens::StringValue("one".into()), tokens[2].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[3].kind);
+        assert_eq!(Tokens::Comma, tokens[4].kind);
+        assert_eq!(Tokens::NumberValue("2".into()), tokens[5].kind);
+        assert_eq!(Tokens::Comma, tokens[6].kind);
+
+        assert_eq!(Tokens::LeftBrace, tokens[7].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[8].kind);
+        assert_eq!(Tokens::StringValue("inner key".into()), tokens[9].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[10].kind);
+        assert_eq!(Tokens::Colon, tokens[11].kind);
+        assert_eq!(Tokens::BooleanValue(true), tokens[12].kind);
+        assert_eq!(Tokens::RightBrace, tokens[13].kind);
+        assert_eq!(Tokens::RightBracket, tokens[14].kind);
+    }
+
+    #[test]
+    fn test_tokenize_quote() {
+        let input: String = "{\"key\": \"\\\"\"}".into();
+
+        let tokens = tokenize(&input).unwrap();
+
+        assert_eq!(tokens.len(), 10);
+        assert_eq!(Tokens::LeftBrace, tokens[0].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[1].kind);
+        assert_eq!(Tokens::StringValue("key".into()), tokens[2].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[3].kind);
+        assert_eq!(Tokens::Colon, tokens[4].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[5].kind);
+        assert_eq!(Tokens::StringValue("\"".into()), tokens[6].kind);
+        assert_eq!(Tokens::DoubleQuote, tokens[7].kind);
+        assert_eq!(Tokens::RightBrace, tokens[8].kind);
+    }
+
+    #[test]
+    fn test_tokenize_spans() {
+        let tokens = tokenize("{\n  \"key\": 12}").unwrap();
+
+        assert_eq!(tokens.len(), 8);
+        assert_eq!(tokens[0].span.start, Position::default());
+        //the string value spans the characters between the quotes
+        assert_eq!(
+            tokens[2].span,
+            Span::new(
+                Position {
+                    offset: 5,
+                    line: 2,
+                    column: 4
+                },
+                Position {
+                    offset: 8,
+                    line: 2,
+                    column: 7
+                }
+            )
+        );
+        assert_eq!(
+            tokens[5].span.start,
+            Position {
+                offset: 11,
+                line: 2,
+                column: 10
+            }
+        );
+        assert_eq!(tokens[7].kind, Tokens::EOF);
+        assert_eq!(tokens[7].span.start.offset, 14);
+    }
+
+    #[test]
+    fn test_tokenize_error_position() {
+        let error = tokenize("[\n  1,\n  ?]").unwrap_err();
+        assert_eq!(
+            error.position(),
+            Some(Position {
+                offset: 9,
+                line: 3,
+                column: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_tokenize_escapes() {
+        let tokens = tokenize(r#"["\"\\\/\b\f\n\r\t", "aé中"]"#).unwrap();
+
+        assert_eq!(
+            Tokens::StringValue("\"\\/\u{8}\u{c}\n\r\t".into()),
+            tokens[2].kind
+        );
+        assert_eq!(Tokens::StringValue("aé中".into()), tokens[6].kind);
+    }
+
+    #[test]
+    fn test_tokenize_surrogate_pair() {
+        let tokens = tokenize(r#"["\ud83d\ude00", "\u00e9"]"#).unwrap();
+        assert_eq!(Tokens::StringValue("😀".into()), tokens[2].kind);
+        assert_eq!(Tokens::StringValue("é".into()), tokens[6].kind);
+    }
+
+    #[test]
+    fn test_tokenize_rejects_invalid_escapes() {
+        for (input, sequence, offset) in [
+            (r#"["\x15"]"#, r"\x", 2),
+            (r#"["ab\017"]"#, r"\0", 4),
+            (r#"["\u12G4"]"#, r"\u12G", 2),
+            (r#"["\ud83d"]"#, r"\uD83D", 2),
+            (r#"["\ud83dA"]"#, r"\uD83D", 2),
+            (r#"["\ud83d\u0041"]"#, r"\u0041", 8),
+            (r#"["\ude00"]"#, r"\uDE00", 2),
+        ] {
+            assert_tokenize_error(
+                input,
+                offset,
+                |error| matches!(error, ParserErrors::InvalidEscape { sequence: found, .. } if found == sequence),
+            );
+        }
+    }
+
+    #[test]
+    fn test_tokenize_rejects_control_characters() {
+        for (input, character, offset) in [
+            ("[\"\ttab\"]", '\t', 2),
+            ("[\"line\nbreak\"]", '\n', 6),
+            ("[\"nul\u{0}\"]", '\u{0}', 5),
+        ] {
+            assert_tokenize_error(
+                input,
+                offset,
+                |error| matches!(error, ParserErrors::ControlCharacter { character: found, .. } if *found == character),
+            );
+        }
+        //escaped they are fine
+        let tokens = tokenize(r#"["\ttab\nline"]"#).unwrap();
+        assert_eq!(Tokens::StringValue("\ttab\nline".into()), tokens[2].kind);
+    }
+
+    #[test]
+    fn test_tokenize_rejects_unterminated_string() {
+        for (input, offset) in [("[\"open", 1), ("{\"key\": \"value\\\"}", 8), ("\"", 0)] {
+            assert_tokenize_error(input, offset, |error| {
+                matches!(error, ParserErrors::UnterminatedString { .. })
+            });
+        }
+    }
+
+    #[test]
+    fn test_tokenize_number_grammar() {
+        for number in [
+            "0",
+            "-0",
+            "7",
+            "-12",
+            "0.5",
+            "-0.25e10",
+            "1E+2",
+            "23456789012E66",
+        ] {
+            let input = format!("[{number}]");
+            let tokens = tokenize(&input).unwrap();
+            assert_eq!(Tokens::NumberValue(number.into()), tokens[1].kind);
+        }
+        //numbers end at the first delimiter
+        let tokens = tokenize("[1,-2]").unwrap();
+        assert_eq!(Tokens::NumberValue("1".into()), tokens[1].kind);
+        assert_eq!(Tokens::NumberValue("-2".into()), tokens[3].kind);
+    }
+
+    #[test]
+    fn test_tokenize_rejects_invalid_numbers() {
+        for (input, number, reason) in [
+            ("[013]", "013", "leading zeros are not allowed"),
+            ("[-01]", "-01", "leading zeros are not allowed"),
+            ("[0x14]", "0x14", "unexpected character in number"),
+            ("[1e]", "1e", "expected digits in the exponent"),
+            ("[0e+]", "0e+", "expected digits in the exponent"),
+            ("[0e+-1]", "0e+-1", "expected digits in the exponent"),
+            ("[1.]", "1.", "expected digits after '.'"),
+            ("[.5]", "", ""),
+            ("[-]", "-", "expected a digit after '-'"),
+            ("[- 1]", "-", "expected a digit after '-'"),
+            ("[1.5.2]", "1.5.2", "unexpected character in number"),
+        ] {
+            assert_tokenize_error(input, 1, |error| match error {
+                ParserErrors::InvalidNumber {
+                    number: found,
+                    reason: found_reason,
+                    ..
+                } => found == number && *found_reason == reason,
+                ParserErrors::TokenizeError(_) => number.is_empty(),
+                _ => false,
+            });
+        }
+    }
+
+    #[test]
+    fn test_tokenize_rejects_unknown_words() {
+        for input in ["[False]", "[truth]", "[nul]", "[alert()]"] {
+            assert!(
+                matches!(tokenize(input), Err(ParserErrors::TokenizeError(_))),
+                "{input}"
+            );
+        }
+    }
+
+    //hands out a single byte per read, so every multi-byte char is split
+    //across reads
+    struct Trickle<'a>(&'a [u8]);
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buffer[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_lexer_reads_across_chunk_boundaries() {
+        let input = "{\"clé\": [\"中😀\", -1.5e3]}";
+        let reader = BufReader::with_capacity(1, Trickle(input.as_bytes()));
+        let tokens: Vec<Token> = Lexer::new(reader).collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(tokens, tokenize(input).unwrap());
+        assert_eq!(Tokens::StringValue("clé".into()), tokens[2].kind);
+        assert_eq!(Tokens::StringValue("中😀".into()), tokens[7].kind);
+        assert_eq!(tokens.last().unwrap().span.end.offset, input.len());
+    }
+
+    #[test]
+    fn test_lexer_yields_tokens_before_reading_everything() {
+        //the reader fails after the first bytes, the tokens before it are
+        //still handed out
+        let reader = "[1, ".as_bytes().chain(FailingReader);
+        let mut lexer = Lexer::new(BufReader::new(reader));
+
+        assert_eq!(lexer.next().unwrap().unwrap().kind, Tokens::LeftBracket);
+        assert_eq!(
+            lexer.next().unwrap().unwrap().kind,
+            Tokens::NumberValue("1".into())
+        );
+        assert_eq!(lexer.next().unwrap().unwrap().kind, Tokens::Comma);
+        assert!(matches!(lexer.next(), Some(Err(ParserErrors::IoError(_)))));
+        assert!(lexer.next().is_none());
+    }
+
+    struct FailingReader;
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk on fire"))
+        }
+    }
+
+    #[test]
+    fn test_lexer_rejects_invalid_utf8() {
+        for (input, offset) in [
+            (&b"[\"ab\xff\"]"[..], 4),
+            (&b"[\"\xc3\x28\"]"[..], 2),
+            //cut off in the middle of a char
+            (&b"[\"\xe4\xb8"[..], 2),
+        ] {
+            assert_tokenize_error(input, offset, |error| {
+                matches!(error, ParserErrors::InvalidUtf8 { .. })
+            });
+        }
+    }
+
+    #[test]
+    fn test_lexer_invalid_utf8_offsets() {
+        for (input, offset) in [
+            //overlong encoding of '/'
+            (&b"[\"a\xc0\xaf\"]"[..], 3),
+            //an encoded surrogate
+            (&b"[1, \"\xed\xa0\x80\"]"[..], 5),
+            //beyond U+10FFFF
+            (&b"\xf4\x90\x80\x80"[..], 0),
+            (&b"[\"\xe4\xb8\xad\x80\"]"[..], 5),
+        ] {
+            assert_tokenize_error(input, offset, |error| {
+                matches!(error, ParserErrors::InvalidUtf8 { .. })
+            });
+        }
+    }
+
+    #[test]
+    fn test_lexer_byte_order_mark() {
+        let input = "\u{feff}[1]";
+        let error = tokenize(input).unwrap_err();
+        assert!(matches!(error, ParserErrors::ParsingError { .. }));
+        assert_eq!(error.position().unwrap().offset, 0);
+
+        let tokens: Vec<Token> = Lexer::for_str(input)
+            .strip_bom(true)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens[0].kind, Tokens::LeftBracket);
+        assert_eq!(tokens[0].span.start.offset, 3);
+        //only a leading one is a byte order mark
+        assert!(Lexer::for_str("[\u{feff}1]")
+            .strip_bom(true)
+            .any(|token| token.is_err()));
+    }
+
+    #[test]
+    fn test_tokenize_only_skips_json_whitespace() {
+        let tokens = tokenize(" \t\r\n[\r\n]\n").unwrap();
+        assert_eq!(tokens.len(), 3);
+        for input in ["[\u{a0}]", "[1,\u{2028}2]", "\u{b}[]", "[\u{c}]"] {
+            assert!(tokenize(input).is_err(), "{input:?}");
+        }
+    }
+}
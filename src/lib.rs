@@ -1,546 +1,805 @@
-use std::{env, fs, iter::Peekable, path::Path};
+use std::{
+    borrow::Cow,
+    env,
+    io::{BufRead, BufReader, Read},
+};
 
-#[derive(Debug, PartialEq, Clone)]
-enum Tokens {
-    LeftBrace,
-    RightBrace,
-    LeftBracket,
-    RightBracket,
-    DoubleQuote,
-    Colon,
-    Comma,
-    StringValue(String),
-    BooleanValue(bool),
-    IntegerValue(i32),
-    FloatValue(f32),
-    NullValue,
-    EOF,
+mod cli;
+mod cst;
+mod diagnostic;
+mod files;
+mod lexer;
+mod position;
+mod report;
+mod ser;
+mod value;
+mod visitor;
+
+pub use cst::{SyntaxNode, SyntaxTree};
+pub use lexer::{Lexer, Token, Tokens};
+pub use position::{Position, Span};
+pub use ser::{
+    to_string, to_string_pretty, to_writer, Indent, JSONSerializer, Newline, SerializerOptions,
+};
+pub use value::{Array, JsonValue, Number, Object};
+pub use visitor::JsonVisitor;
+
+//pulls tokens as the parser asks for them, remembering where the last one
+//ended so running out of tokens can still be reported somewhere
+struct TokenStream<'a, I: Iterator<Item = Result<Token<'a>, ParserErrors>>> {
+    tokens: I,
+    peeked: Option<Token<'a>>,
+    end: Position,
 }
-impl Tokens {
-    fn parse_string_value<'a>(
-        tokens: &mut impl Iterator<Item = &'a Tokens>,
-    ) -> Result<String, ParserErrors> {
-        //the idea is to make sure the correct structure("<value>") exist and
-        //return the StringValue token already parsed in a subset of items
-
-        let Some(token) = tokens.next() else {
-            return Err(ParserErrors::ParsingError(
-                "expected more tokens".to_string(),
-            ));
-        };
+impl<'a, I: Iterator<Item = Result<Token<'a>, ParserErrors>>> TokenStream<'a, I> {
+    fn new(tokens: I) -> Self {
+        TokenStream {
+            tokens,
+            peeked: None,
+            end: Position::default(),
+        }
+    }
 
-        let name = match token {
-            Tokens::StringValue(name) => name,
-            _ => {
-                return Err(ParserErrors::ParsingError(
-                    "expected String Value".to_string(),
-                ))
-            }
-        };
-        //quotes
-        let Some(token) = tokens.next() else {
-            return Err(ParserErrors::ParsingError(
-                "expected more tokens".to_string(),
-            ));
+    fn next(&mut self) -> Result<Option<Token<'a>>, ParserErrors> {
+        if let Some(token) = self.peeked.take() {
+            return Ok(Some(token));
+        }
+        let Some(token) = self.tokens.next().transpose()? else {
+            return Ok(None);
         };
-        if *token != Tokens::DoubleQuote {
-            return Err(ParserErrors::ParsingError(
-                "expected double quote(\")".to_string(),
-            ));
+        self.end = token.span.end;
+        Ok(Some(token))
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token<'a>>, ParserErrors> {
+        if self.peeked.is_none() {
+            self.peeked = self.next()?;
         }
-        Ok(name.clone())
+        Ok(self.peeked.as_ref())
     }
 }
 
-fn tokenize(input: String) -> Result<Vec<Tokens>, ParserErrors> {
-    let mut tokens: Vec<Tokens> = Vec::new();
-    let mut chars = input.chars().peekable();
-    while let Some(c) = chars.next() {
-        //eat the whitespace nom nom nom
-        if c.is_whitespace() {
-            continue;
-        }
-
-        match c {
-            '{' => tokens.push(Tokens::LeftBrace),
-            '}' => tokens.push(Tokens::RightBrace),
-            '[' => tokens.push(Tokens::LeftBracket),
-            ']' => tokens.push(Tokens::RightBracket),
-            '"' => {
-                tokens.push(Tokens::DoubleQuote);
-                let mut buffer: String = String::new();
-                while let Some(c) = chars.next() {
-                    //don't eat the whitespace inside quotes
-                    if c == '"' {
-                        let Some(peek_c) = chars.peek() else {
-                            return Err(ParserErrors::TokenizeError);
-                        };
-                        if *peek_c != ',' && *peek_c != ':' && *peek_c != ']' && *peek_c != '}' {
-                            //this is a escaped double quote
-                            buffer.push(c);
-                            continue;
-                        }
-                        tokens.push(Tokens::StringValue(buffer));
-                        tokens.push(Tokens::DoubleQuote);
-                        break;
-                    }
-                    buffer.push(c);
+fn parsing_error(message: &str, position: Position) -> ParserErrors {
+    ParserErrors::ParsingError {
+        message: message.to_string(),
+        position,
+    }
+}
+
+/// What [`Events`] reads from the document.
+///
+/// A value is a flat run of events, lists and objects being bracketed by their
+/// start and end events. Inside an object every value follows its key. Keys
+/// and strings borrow from the input like they do in [`JsonValue`].
+#[derive(Debug, PartialEq, Clone)]
+pub enum Event<'a> {
+    StartObject,
+    Key(Cow<'a, str>),
+    EndObject,
+    StartArray,
+    EndArray,
+    String(Cow<'a, str>),
+    Number(Number),
+    Bool(bool),
+    Null,
+}
+
+//where a list or an object is between its delimiters, which decides the
+//tokens allowed next
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Expect {
+    //right after the opening delimiter: the first element or the closing one
+    FirstOrClose,
+    //right after a comma: another element
+    Element,
+    //right after an object key and its colon: the member value
+    MemberValue,
+    //right after an element: a comma or the closing delimiter
+    CommaOrClose,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Container {
+    Object,
+    List,
+}
+
+//turns tokens into events. The open lists and objects live on an explicit
+//stack instead of the call stack, so how deep a document can nest is only
+//bounded by memory and max_depth
+struct EventParser<'a, 'o, I: Iterator<Item = Result<Token<'a>, ParserErrors>>> {
+    tokens: TokenStream<'a, I>,
+    options: &'o ParserOptions,
+    stack: Vec<(Container, Expect)>,
+}
+
+impl<'a, 'o, I: Iterator<Item = Result<Token<'a>, ParserErrors>>> EventParser<'a, 'o, I> {
+    fn new(tokens: TokenStream<'a, I>, options: &'o ParserOptions) -> Self {
+        EventParser {
+            tokens,
+            options,
+            stack: Vec::new(),
+        }
+    }
+
+    //the next event of the value being read, starting a new value when no
+    //list or object is open
+    fn next_event(&mut self) -> Result<(Event<'a>, Span), ParserErrors> {
+        loop {
+            let token = self.next_token()?;
+            let Some((container, expect)) = self.stack.last().copied() else {
+                return self.value_event(token);
+            };
+            match (container, expect, &token.kind) {
+                (Container::Object, Expect::MemberValue, _) => {
+                    self.set_expect(Expect::CommaOrClose);
+                    return self.value_event(token);
                 }
-            }
-            ':' => tokens.push(Tokens::Colon),
-            ',' => tokens.push(Tokens::Comma),
-            _ => {
-                if c.is_alphanumeric() || c == '-' || c == '+' {
-                    let mut buffer: String = c.to_string();
-
-                    while let Some(c) = chars.peek() {
-                        if !c.is_alphanumeric() && *c != '.' && *c != '-' && *c != '+' {
-                            if buffer == "true" {
-                                tokens.push(Tokens::BooleanValue(true));
-                            } else if buffer == "false" {
-                                tokens.push(Tokens::BooleanValue(false));
-                            } else if buffer == "null" {
-                                tokens.push(Tokens::NullValue);
-                            } else if buffer.contains(".")
-                                || buffer.contains("e")
-                                || buffer.contains("E")
-                            {
-                                println!("{buffer}");
-                                let float = buffer.parse::<f32>()?;
-                                tokens.push(Tokens::FloatValue(float));
-                            } else {
-                                let integer = buffer.parse::<i32>()?;
-                                tokens.push(Tokens::IntegerValue(integer));
-                            }
-                            break;
-                        }
-                        let c = match chars.next() {
-                            Some(c) => c,
-                            None => break,
-                        };
-                        buffer.push(c);
+                (Container::Object, _, Tokens::EOF) => {
+                    return Err(parsing_error(
+                        "expected '}' to close the object",
+                        token.span.start,
+                    ))
+                }
+                (Container::List, _, Tokens::EOF) => {
+                    return Err(parsing_error(
+                        "expected ']' to close the list",
+                        token.span.start,
+                    ))
+                }
+                // empty object or end of the members
+                (
+                    Container::Object,
+                    Expect::FirstOrClose | Expect::CommaOrClose,
+                    Tokens::RightBrace,
+                ) => {
+                    self.stack.pop();
+                    return Ok((Event::EndObject, token.span));
+                }
+                //empty list or end of the elements
+                (
+                    Container::List,
+                    Expect::FirstOrClose | Expect::CommaOrClose,
+                    Tokens::RightBracket,
+                ) => {
+                    self.stack.pop();
+                    return Ok((Event::EndArray, token.span));
+                }
+                (Container::Object, Expect::Element, Tokens::RightBrace) => {
+                    return Err(parsing_error("trailing comma before '}'", token.span.start))
+                }
+                (Container::List, Expect::Element, Tokens::RightBracket) => {
+                    return Err(parsing_error("trailing comma before ']'", token.span.start))
+                }
+                (_, Expect::CommaOrClose, Tokens::Comma) => {
+                    self.set_expect(Expect::Element);
+                }
+                (Container::Object, Expect::CommaOrClose, _) => {
+                    return Err(parsing_error(
+                        "expected ',' or '}' after object member",
+                        token.span.start,
+                    ))
+                }
+                (Container::List, Expect::CommaOrClose, _) => {
+                    return Err(parsing_error(
+                        "expected ',' or ']' after list element",
+                        token.span.start,
+                    ))
+                }
+                (_, _, Tokens::Comma) => {
+                    return Err(parsing_error("unexpected ','", token.span.start))
+                }
+                (Container::Object, _, Tokens::DoubleQuote) => {
+                    let (key, span) = self.string_value(&token)?;
+
+                    let token = self.next_token()?;
+                    if token.kind != Tokens::Colon {
+                        return Err(parsing_error(
+                            "expected ':' after object key",
+                            token.span.start,
+                        ));
                     }
-                } else {
-                    println!("tokenix {:?}", tokens);
-                    return Err(ParserErrors::TokenizeError);
+                    self.set_expect(Expect::MemberValue);
+                    return Ok((Event::Key(key), span));
+                }
+                (Container::Object, _, _) => {
+                    return Err(parsing_error(
+                        "expected a quoted object key",
+                        token.span.start,
+                    ))
+                }
+                (Container::List, _, _) => {
+                    self.set_expect(Expect::CommaOrClose);
+                    return self.value_event(token);
                 }
             }
         }
     }
-    tokens.push(Tokens::EOF);
-    Ok(tokens)
-}
 
-fn parse_object<'a>(
-    tokens: &mut Peekable<impl Iterator<Item = &'a Tokens>>,
-) -> Result<(), ParserErrors> {
-    while let Some(token) = tokens.next() {
-        match token {
-            Tokens::EOF => break,
-            Tokens::LeftBrace => continue,
-            Tokens::RightBrace => {
-                // empty object
-                //Check if next value is not { means no more objects
-                // continue;
-                break;
+    //reads the value starting at `token`: scalars are read whole while lists
+    //and objects are only opened
+    fn value_event(&mut self, token: Token<'a>) -> Result<(Event<'a>, Span), ParserErrors> {
+        let event = match token.kind {
+            Tokens::LeftBrace => {
+                self.open(Container::Object, &token)?;
+                Event::StartObject
             }
-            Tokens::Comma => continue,
-            _ => {
-                if *token != Tokens::DoubleQuote {
-                    return Err(ParserErrors::ParsingError(
-                        "expected double quote(\")".to_string(),
-                    ));
-                }
-                //todo skip { if new iteration the function enters with the { already consumed
-                // let key =Tokens::parse_string_value(tokens)?;
-                Tokens::parse_string_value(tokens)?;
-
-                let Some(token) = tokens.next() else {
-                    return Err(ParserErrors::ParsingError(
-                        "expected more token".to_string(),
-                    ));
+            Tokens::LeftBracket => {
+                self.open(Container::List, &token)?;
+                Event::StartArray
+            }
+            Tokens::DoubleQuote => {
+                let (string, span) = self.string_value(&token)?;
+                return Ok((Event::String(string), span));
+            }
+            Tokens::NumberValue(number) => {
+                let Some(parsed) = Number::from_literal(&number, self.options.arbitrary_precision)
+                else {
+                    return Err(ParserErrors::InvalidNumber {
+                        number,
+                        reason: "number out of range",
+                        position: token.span.start,
+                    });
                 };
-                if *token != Tokens::Colon {
-                    return Err(ParserErrors::ParsingError(
-                        "expected colon token".to_string(),
-                    ));
-                }
+                Event::Number(parsed)
+            }
+            Tokens::BooleanValue(boolean) => Event::Bool(boolean),
+            Tokens::NullValue => Event::Null,
+            _ => return Err(parsing_error("expected a value", token.span.start)),
+        };
+        Ok((event, token.span))
+    }
 
-                let Some(token) = tokens.peek() else {
-                    return Err(ParserErrors::ParsingError("expected token".to_string()));
-                };
+    //the idea is to make sure the correct structure("<value>") exist and
+    //return the StringValue token already parsed, spanning both quotes
+    fn string_value(&mut self, open: &Token) -> Result<(Cow<'a, str>, Span), ParserErrors> {
+        let token = self.next_token()?;
+        let Tokens::StringValue(string) = token.kind else {
+            return Err(parsing_error("expected a string", token.span.start));
+        };
+        //quotes
+        let token = self.next_token()?;
+        if token.kind != Tokens::DoubleQuote {
+            return Err(parsing_error("expected closing '\"'", token.span.start));
+        }
+        Ok((string, Span::new(open.span.start, token.span.end)))
+    }
 
-                match *token {
-                    Tokens::LeftBrace => {
-                        parse_object(tokens)?;
-                    }
-                    Tokens::LeftBracket => parse_list(tokens)?,
-                    Tokens::DoubleQuote => {
-                        let Some(_) = tokens.next() else {
-                            return Err(ParserErrors::ParsingError(
-                                "expected more tokens".to_string(),
-                            ));
-                        };
-                        let Some(token) = tokens.next() else {
-                            return Err(ParserErrors::ParsingError(
-                                "expected more tokens".to_string(),
-                            ));
-                        };
-                        parse_value(token)?;
-                        let Some(token) = tokens.next() else {
-                            return Err(ParserErrors::ParsingError(
-                                "expected \" double quote".to_string(),
-                            ));
-                        };
-                        if *token != Tokens::DoubleQuote {
-                            return Err(ParserErrors::ParsingError(
-                                "expected \" double quote".to_string(),
-                            ));
-                        }
-                    }
-                    _ => {
-                        let Some(token) = tokens.next() else {
-                            return Err(ParserErrors::ParsingError(
-                                "expected more tokens".to_string(),
-                            ));
-                        };
-                        parse_value(token)?;
-                    }
-                };
+    fn next_token(&mut self) -> Result<Token<'a>, ParserErrors> {
+        match self.tokens.next()? {
+            Some(token) => Ok(token),
+            None => Err(parsing_error("unexpected end of input", self.tokens.end)),
+        }
+    }
+
+    fn open(&mut self, container: Container, token: &Token) -> Result<(), ParserErrors> {
+        let depth = self.stack.len() + 1;
+        if let Some(max_depth) = self.options.max_depth {
+            if depth > max_depth {
+                return Err(ParserErrors::DepthLimitExceeded {
+                    depth,
+                    position: token.span.start,
+                });
+            }
+        }
+        self.stack.push((container, Expect::FirstOrClose));
+        Ok(())
+    }
+
+    //checks there is a document at all before reading it
+    fn start_document(&mut self) -> Result<(), ParserErrors> {
+        if let Some(token) = self.tokens.peek()? {
+            if token.kind == Tokens::EOF {
+                return Err(parsing_error("empty document", token.span.end));
+            }
+        }
+        self.check_root()
+    }
+
+    //makes sure the next top level value is one the options allow
+    fn check_root(&mut self) -> Result<(), ParserErrors> {
+        let Some(token) = self.tokens.peek()? else {
+            return Err(parsing_error("unexpected end of input", self.tokens.end));
+        };
+        if !self.options.allow_scalar_root
+            && !matches!(token.kind, Tokens::LeftBrace | Tokens::LeftBracket)
+        {
+            return Err(parsing_error(
+                "expected '{' or '[' at the start of the document",
+                token.span.start,
+            ));
+        }
+        Ok(())
+    }
+
+    //only the end of the input may follow the document
+    fn end_document(&mut self) -> Result<(), ParserErrors> {
+        if let Some(token) = self.tokens.next()? {
+            if token.kind != Tokens::EOF {
+                return Err(parsing_error(
+                    "unexpected content after the document",
+                    token.span.start,
+                ));
             }
         }
+        Ok(())
+    }
+
+    fn set_expect(&mut self, expect: Expect) {
+        if let Some(top) = self.stack.last_mut() {
+            top.1 = expect;
+        }
     }
-    Ok(())
 }
 
-fn parse_list<'a>(
-    tokens: &mut Peekable<impl Iterator<Item = &'a Tokens>>,
-) -> Result<(), ParserErrors> {
-    while let Some(token) = tokens.next() {
-        match token {
-            Tokens::EOF => break,
-            Tokens::LeftBracket => continue,
-            Tokens::RightBracket =>
-            //empty list
-            {
-                // continue;
-                break;
+//a list or object whose elements are still being read
+enum Partial<'a> {
+    List(Vec<JsonValue<'a>>),
+    Object(Vec<(Cow<'a, str>, JsonValue<'a>)>, Option<Cow<'a, str>>),
+}
+
+//pulls the events of one value and assembles them into a tree
+fn build_value<'a>(
+    parser: &mut EventParser<'a, '_, impl Iterator<Item = Result<Token<'a>, ParserErrors>>>,
+) -> Result<JsonValue<'a>, ParserErrors> {
+    let mut stack: Vec<Partial> = Vec::new();
+    loop {
+        let (event, _) = parser.next_event()?;
+        let value = match event {
+            Event::StartObject => {
+                stack.push(Partial::Object(Vec::new(), None));
+                continue;
             }
-            Tokens::Comma => continue,
-            Tokens::LeftBrace => parse_object(tokens)?,
-            Tokens::DoubleQuote => {
-                let Some(token) = tokens.next() else {
-                    return Err(ParserErrors::ParsingError(
-                        "expected more tokens".to_string(),
-                    ));
-                };
-                parse_value(token)?;
-                let Some(token) = tokens.next() else {
-                    return Err(ParserErrors::ParsingError(
-                        "expected \" double quote".to_string(),
-                    ));
-                };
-                if *token != Tokens::DoubleQuote {
-                    return Err(ParserErrors::ParsingError(
-                        "expected \" double quote".to_string(),
-                    ));
+            Event::StartArray => {
+                stack.push(Partial::List(Vec::new()));
+                continue;
+            }
+            Event::Key(name) => {
+                if let Some(Partial::Object(_, key)) = stack.last_mut() {
+                    *key = Some(name);
                 }
+                continue;
+            }
+            Event::EndObject | Event::EndArray => match stack.pop() {
+                Some(Partial::Object(members, _)) => JsonValue::Object(members.into()),
+                Some(Partial::List(elements)) => JsonValue::Array(elements.into()),
+                None => unreachable!("the parser only ends what it started"),
+            },
+            Event::String(string) => JsonValue::String(string),
+            Event::Number(number) => JsonValue::Number(number),
+            Event::Bool(boolean) => JsonValue::Bool(boolean),
+            Event::Null => JsonValue::Null,
+        };
+        match stack.last_mut() {
+            None => return Ok(value),
+            Some(Partial::List(elements)) => elements.push(value),
+            Some(Partial::Object(members, key)) => {
+                let key = key.take().unwrap_or_default();
+                members.push((key, value));
             }
-            _ => parse_value(token)?,
         }
     }
-    Ok(())
 }
 
-fn parse_value(token: &Tokens) -> Result<(), ParserErrors> {
-    match token {
-        Tokens::StringValue(_) => (),
-        Tokens::IntegerValue(_) => (),
-        Tokens::FloatValue(_) => (),
-        Tokens::BooleanValue(_) => (),
-        Tokens::NullValue => (),
-        _ => return Err(ParserErrors::ParsingError("unexpected token".to_string())),
-    };
-    Ok(())
+//pulls the events of one value and throws them away, only the open lists and
+//objects are kept so any size of document is checked in constant memory
+fn skip_value<'a>(
+    parser: &mut EventParser<'a, '_, impl Iterator<Item = Result<Token<'a>, ParserErrors>>>,
+) -> Result<(), ParserErrors> {
+    loop {
+        parser.next_event()?;
+        if parser.stack.is_empty() {
+            return Ok(());
+        }
+    }
 }
 
-struct JSONDocument {}
-impl JSONDocument {
-    fn parse(&mut self, tokens: Vec<Tokens>) -> Result<bool, ParserErrors> {
-        if tokens.len() < 2 {
-            return Err(ParserErrors::ParsingError(
-                "not enough elements".to_string(),
-            ));
+//pulls the events of one value and hands them to the visitor
+fn visit_value<'a>(
+    parser: &mut EventParser<'a, '_, impl Iterator<Item = Result<Token<'a>, ParserErrors>>>,
+    visitor: &mut impl JsonVisitor,
+) -> Result<(), ParserErrors> {
+    loop {
+        let (event, span) = parser.next_event()?;
+        visitor::dispatch(visitor, event, span);
+        if parser.stack.is_empty() {
+            return Ok(());
         }
+    }
+}
 
-        let mut tokens = tokens.iter().peekable();
+/// A pull parser handing out the [`Event`]s of a single document together
+/// with the part of the input each one was read from.
+///
+/// Nothing is kept of the events already read, so a document can be processed
+/// without building it. The iterator ends after the document or the first
+/// error. Created with [`JSONDocument::events_str`] or
+/// [`JSONDocument::events_reader`].
+///
+/// ```
+/// use json_parser::{Event, JSONDocument};
+///
+/// let json_document = JSONDocument::default();
+/// let keys: Vec<String> = json_document
+///     .events_str("{\"id\": 1, \"tags\": [\"a\"]}")
+///     .filter_map(|event| match event {
+///         Ok((Event::Key(key), _)) => Some(key.into_owned()),
+///         _ => None,
+///     })
+///     .collect();
+/// assert_eq!(keys, vec!["id", "tags"]);
+/// ```
+pub struct Events<'a, 'o, R: BufRead> {
+    parser: EventParser<'a, 'o, Lexer<'a, R>>,
+    progress: Progress,
+}
 
-        let Some(token) = tokens.peek() else {
-            return Err(ParserErrors::ParsingError("expected token".to_string()));
-        };
-        match token {
-            Tokens::LeftBrace => parse_object(&mut tokens)?,
-            Tokens::LeftBracket => parse_list(&mut tokens)?,
-            _ => {
-                return Err(ParserErrors::ParsingError(
-                    "invalid inital token".to_string(),
-                ))
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Progress {
+    Start,
+    InDocument,
+    //the whole document was read, only the end of the input may follow
+    End,
+    Done,
+}
+
+impl<'a, R: BufRead> Events<'a, '_, R> {
+    fn read_event(&mut self) -> Result<Option<(Event<'a>, Span)>, ParserErrors> {
+        match self.progress {
+            Progress::Start => self.parser.start_document()?,
+            Progress::InDocument => (),
+            Progress::End => {
+                self.progress = Progress::Done;
+                self.parser.end_document()?;
+                return Ok(None);
             }
+            Progress::Done => return Ok(None),
+        }
+        let event = self.parser.next_event()?;
+        self.progress = if self.parser.stack.is_empty() {
+            Progress::End
+        } else {
+            Progress::InDocument
         };
+        Ok(Some(event))
+    }
+}
 
-        Ok(true)
+impl<'a, R: BufRead> Iterator for Events<'a, '_, R> {
+    type Item = Result<(Event<'a>, Span), ParserErrors>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let event = self.read_event();
+        if event.is_err() {
+            self.progress = Progress::Done;
+        }
+        event.transpose()
     }
 }
 
-struct Config {
-    file_path: String,
+/// The documents of a stream, read one after the other by
+/// [`JSONDocument::stream_reader`].
+///
+/// Each document is built as it's asked for, and reading stops after the
+/// first error.
+pub struct Documents<'a, 'o, R: BufRead> {
+    parser: EventParser<'a, 'o, Lexer<'a, R>>,
+    done: bool,
+}
+
+impl<'a, R: BufRead> Documents<'a, '_, R> {
+    fn read_document(&mut self) -> Result<Option<JsonValue<'a>>, ParserErrors> {
+        match self.parser.tokens.peek()? {
+            Some(token) if token.kind != Tokens::EOF => (),
+            _ => return Ok(None),
+        }
+        self.parser.check_root()?;
+        build_value(&mut self.parser).map(Some)
+    }
+}
+
+impl<'a, R: BufRead> Iterator for Documents<'a, '_, R> {
+    type Item = Result<JsonValue<'a>, ParserErrors>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let document = self.read_document();
+        if !matches!(document, Ok(Some(_))) {
+            self.done = true;
+        }
+        document.transpose()
+    }
+}
+
+/// Settings for [`JSONDocument`].
+#[derive(Debug, Clone)]
+pub struct ParserOptions {
+    /// Keep every number as written in [`Number::Raw`] instead of converting
+    /// it, so nothing is lost to rounding or overflow.
+    pub arbitrary_precision: bool,
+    /// Accept strings, numbers, booleans and null as the whole document, as
+    /// RFC 8259 does. Turn it off for the legacy RFC 4627 rule that the
+    /// document has to be an object or a list.
+    pub allow_scalar_root: bool,
+    /// How many lists and objects may be nested inside each other, `None`
+    /// for no limit. Documents nested deeper fail with
+    /// [`ParserErrors::DepthLimitExceeded`] instead of exhausting the stack.
+    pub max_depth: Option<usize>,
+    /// Skip a UTF-8 byte order mark at the start of the input. RFC 8259 lets
+    /// parsers ignore one, but it is an error unless this is set.
+    pub allow_bom: bool,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            arbitrary_precision: false,
+            allow_scalar_root: true,
+            max_depth: Some(128),
+            allow_bom: false,
+        }
+    }
+}
+
+/// A JSON parser configured with [`ParserOptions`].
+///
+/// ```
+/// use json_parser::{JSONDocument, JsonValue, Number, ParserOptions};
+///
+/// let json_document = JSONDocument::new(ParserOptions {
+///     arbitrary_precision: true,
+///     ..Default::default()
+/// });
+/// let value = json_document.parse_str("[3.141592653589793238462643383279]").unwrap();
+/// assert_eq!(
+///     value,
+///     JsonValue::Array(vec![JsonValue::Number(Number::Raw(
+///         "3.141592653589793238462643383279".into()
+///     ))].into())
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct JSONDocument {
+    options: ParserOptions,
+}
+impl JSONDocument {
+    pub fn new(options: ParserOptions) -> JSONDocument {
+        JSONDocument { options }
+    }
+
+    /// Parses a JSON document from a string. Strings and keys without escapes
+    /// are borrowed from `input` instead of copied.
+    pub fn parse_str<'a>(&self, input: &'a str) -> Result<JsonValue<'a>, ParserErrors> {
+        self.parse(self.str_lexer(input), build_value)
+    }
+
+    /// Parses a JSON document from UTF-8 encoded bytes.
+    pub fn parse_slice(&self, input: &[u8]) -> Result<JsonValue<'static>, ParserErrors> {
+        self.parse(
+            Lexer::new(input).strip_bom(self.options.allow_bom),
+            build_value,
+        )
+    }
+
+    /// Parses a stream of JSON documents that follow each other, such as
+    /// newline delimited JSON. Whitespace between the documents is optional
+    /// when they can be told apart without it.
+    ///
+    /// ```
+    /// use json_parser::{JSONDocument, JsonValue};
+    ///
+    /// let documents = JSONDocument::default()
+    ///     .parse_str_stream("{\"id\": 1}\n{\"id\": 2}\n")
+    ///     .unwrap();
+    /// assert_eq!(documents.len(), 2);
+    /// ```
+    pub fn parse_str_stream<'a>(&self, input: &'a str) -> Result<Vec<JsonValue<'a>>, ParserErrors> {
+        self.documents(self.str_lexer(input)).collect()
+    }
+
+    /// Reads the stream of JSON documents `reader` holds one document at a
+    /// time, so the whole stream never has to be in memory at once.
+    ///
+    /// ```
+    /// use json_parser::{JSONDocument, JsonValue};
+    ///
+    /// let json_document = JSONDocument::default();
+    /// let mut documents = json_document.stream_reader("[1]\n[2]\n".as_bytes());
+    /// assert!(matches!(documents.next(), Some(Ok(JsonValue::Array(_)))));
+    /// assert!(matches!(documents.next(), Some(Ok(JsonValue::Array(_)))));
+    /// assert!(documents.next().is_none());
+    /// ```
+    pub fn stream_reader<R: Read>(&self, reader: R) -> Documents<'static, '_, BufReader<R>> {
+        self.documents(self.reader_lexer(reader))
+    }
+
+    /// Parses the JSON document `reader` holds, reading it in chunks as the
+    /// parser goes.
+    pub fn parse_reader(&self, reader: impl Read) -> Result<JsonValue<'static>, ParserErrors> {
+        self.parse(self.reader_lexer(reader), build_value)
+    }
+
+    /// Checks that `reader` holds a valid JSON document without building it.
+    ///
+    /// Memory use only grows with how deep the document nests, not with its
+    /// size, so this works for files that wouldn't fit in memory.
+    ///
+    /// ```
+    /// use json_parser::JSONDocument;
+    ///
+    /// let json_document = JSONDocument::default();
+    /// assert!(json_document.validate_reader("[1, 2, 3]".as_bytes()).is_ok());
+    /// assert!(json_document.validate_reader("[1, 2,]".as_bytes()).is_err());
+    /// ```
+    pub fn validate_reader(&self, reader: impl Read) -> Result<(), ParserErrors> {
+        self.parse(self.reader_lexer(reader), skip_value)
+    }
+
+    /// Reads the document in `input`, calling `visitor` for each part of it.
+    pub fn visit_str(
+        &self,
+        input: &str,
+        visitor: &mut impl JsonVisitor,
+    ) -> Result<(), ParserErrors> {
+        self.parse(self.str_lexer(input), |parser| visit_value(parser, visitor))
+    }
+
+    /// Reads the document `reader` holds in chunks, calling `visitor` for each
+    /// part of it.
+    pub fn visit_reader(
+        &self,
+        reader: impl Read,
+        visitor: &mut impl JsonVisitor,
+    ) -> Result<(), ParserErrors> {
+        self.parse(self.reader_lexer(reader), |parser| {
+            visit_value(parser, visitor)
+        })
+    }
+
+    /// Parses the document in `input` into a [`SyntaxTree`] that keeps its
+    /// formatting, for editing it and writing it back.
+    pub fn parse_cst<'a>(&self, input: &'a str) -> Result<SyntaxTree<'a>, ParserErrors> {
+        cst::build(input, self.events_str(input))
+    }
+
+    /// Reads the document in `input` as a stream of [`Events`].
+    pub fn events_str<'a>(&self, input: &'a str) -> Events<'a, '_, &'a [u8]> {
+        self.events(self.str_lexer(input))
+    }
+
+    /// Reads the document `reader` holds as a stream of [`Events`], pulling
+    /// bytes from it as the events are asked for.
+    pub fn events_reader<R: Read>(&self, reader: R) -> Events<'static, '_, BufReader<R>> {
+        self.events(self.reader_lexer(reader))
+    }
+
+    fn str_lexer<'a>(&self, input: &'a str) -> Lexer<'a, &'a [u8]> {
+        Lexer::for_str(input).strip_bom(self.options.allow_bom)
+    }
+
+    fn reader_lexer<R: Read>(&self, reader: R) -> Lexer<'static, BufReader<R>> {
+        Lexer::new(BufReader::new(reader)).strip_bom(self.options.allow_bom)
+    }
+
+    fn events<'a, R: BufRead>(&self, lexer: Lexer<'a, R>) -> Events<'a, '_, R> {
+        Events {
+            parser: EventParser::new(TokenStream::new(lexer), &self.options),
+            progress: Progress::Start,
+        }
+    }
+
+    fn documents<'a, R: BufRead>(&self, lexer: Lexer<'a, R>) -> Documents<'a, '_, R> {
+        Documents {
+            parser: EventParser::new(TokenStream::new(lexer), &self.options),
+            done: false,
+        }
+    }
+
+    //reads a single document with `read_value`, which either builds or skips it
+    fn parse<'a, I, T>(
+        &self,
+        tokens: I,
+        read_value: impl FnOnce(&mut EventParser<'a, '_, I>) -> Result<T, ParserErrors>,
+    ) -> Result<T, ParserErrors>
+    where
+        I: Iterator<Item = Result<Token<'a>, ParserErrors>>,
+    {
+        let mut parser = EventParser::new(TokenStream::new(tokens), &self.options);
+        parser.start_document()?;
+        let value = read_value(&mut parser)?;
+        parser.end_document()?;
+        Ok(value)
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum ParserErrors {
     #[error("{0}")]
     ArgumentError(String),
-    #[error("not able to tokenize")]
-    TokenizeError,
-    #[error("invalid json: {0}")]
-    ParsingError(String),
+    #[error("not able to tokenize at {0}")]
+    TokenizeError(Position),
+    #[error("invalid json: {message} at {position}")]
+    ParsingError { message: String, position: Position },
+    #[error("invalid escape sequence `{sequence}` at {position}")]
+    InvalidEscape {
+        sequence: String,
+        position: Position,
+    },
+    #[error("unescaped control character {character:?} in string at {position}")]
+    ControlCharacter { character: char, position: Position },
+    #[error("unterminated string starting at {position}")]
+    UnterminatedString { position: Position },
+    #[error("nesting depth {depth} exceeds the limit at {position}")]
+    DepthLimitExceeded { depth: usize, position: Position },
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
-    #[error("Parse Int Error: {0}")]
-    ParseIntError(#[from] std::num::ParseIntError),
-    #[error("Parse Float Error: {0}")]
-    ParseFloatError(#[from] std::num::ParseFloatError),
+    #[error("invalid number `{number}`: {reason} at {position}")]
+    InvalidNumber {
+        number: String,
+        reason: &'static str,
+        position: Position,
+    },
+    #[error("invalid UTF-8 sequence at {position}")]
+    InvalidUtf8 { position: Position },
 }
 
-impl Config {
-    fn build(args: Vec<String>) -> Result<Config, ParserErrors> {
-        if args.len() <= 1 {
-            return Err(ParserErrors::ArgumentError(
-                "please provide a filename".to_string(),
-            ));
-        } else if args.len() > 2 {
-            return Err(ParserErrors::ArgumentError(
-                "too many arguments".to_string(),
-            ));
+impl ParserErrors {
+    /// Where in the input the error was found, if it came from the input.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            ParserErrors::TokenizeError(position)
+            | ParserErrors::ParsingError { position, .. }
+            | ParserErrors::InvalidEscape { position, .. }
+            | ParserErrors::ControlCharacter { position, .. }
+            | ParserErrors::UnterminatedString { position }
+            | ParserErrors::InvalidNumber { position, .. }
+            | ParserErrors::DepthLimitExceeded { position, .. }
+            | ParserErrors::InvalidUtf8 { position } => Some(*position),
+            ParserErrors::ArgumentError(_) | ParserErrors::IoError(_) => None,
         }
-        Ok(Config {
-            file_path: args[1].clone(),
-        })
     }
 }
 
-pub fn run() -> Result<(), ParserErrors> {
-    let config = Config::build(env::args().collect())?;
-
-    let path = Path::new(config.file_path.as_str());
-    let data = match fs::read_to_string(path) {
-        Ok(data) => data,
-        Err(e) => return Err(ParserErrors::IoError(e)),
-    };
+/// Parses a JSON document from a string, borrowing the strings that need no
+/// unescaping from `input`.
+///
+/// ```
+/// let value = json_parser::from_str("{\"key\": [1, true]}").unwrap();
+/// assert_eq!(value.get("key").unwrap().as_array().unwrap().len(), 2);
+/// ```
+pub fn from_str(input: &str) -> Result<JsonValue<'_>, ParserErrors> {
+    JSONDocument::default().parse_str(input)
+}
 
-    let tokens = tokenize(data)?;
+/// Parses a JSON document from UTF-8 encoded bytes.
+pub fn from_slice(input: &[u8]) -> Result<JsonValue<'static>, ParserErrors> {
+    JSONDocument::default().parse_slice(input)
+}
 
-    let json_document = &mut JSONDocument {};
-    json_document.parse(tokens)?;
+/// Parses the JSON document `reader` holds, reading it in chunks as the
+/// parser goes.
+pub fn from_reader(reader: impl Read) -> Result<JsonValue<'static>, ParserErrors> {
+    JSONDocument::default().parse_reader(reader)
+}
 
-    println!("valid json!");
-    Ok(())
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    Ok(cli::run(env::args().collect())?)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    //tokenize tests
-    #[test]
-    fn test_tokenize_on_braces() {
-        let tokens = tokenize("{}".into()).unwrap();
-
-        assert_eq!(tokens.len(), 3);
-        assert_eq!(Tokens::LeftBrace, tokens[0]);
-        assert_eq!(Tokens::RightBrace, tokens[1]);
-    }
-    #[test]
-    fn test_tokenize_string_values() {
-        //{"key": "value"}
-        let tokens = tokenize("{\"key\": \"value\"}".into()).unwrap();
-        assert_eq!(tokens.len(), 10);
-        assert_eq!(Tokens::LeftBrace, tokens[0]);
-        assert_eq!(Tokens::DoubleQuote, tokens[1]);
-        assert_eq!(Tokens::StringValue("key".into()), tokens[2]);
-        assert_eq!(Tokens::DoubleQuote, tokens[3]);
-        assert_eq!(Tokens::Colon, tokens[4]);
-        assert_eq!(Tokens::DoubleQuote, tokens[5]);
-        assert_eq!(Tokens::StringValue("value".into()), tokens[6]);
-        assert_eq!(Tokens::DoubleQuote, tokens[7]);
-        assert_eq!(Tokens::RightBrace, tokens[8]);
-    }
-
-    #[test]
-    fn test_tokenize_errors_on_unknown() {
-        assert!(tokenize("?".into()).is_err());
-    }
-
-    #[test]
-    fn test_tokenize_bool_values() {
-        let tokens = tokenize("{\"key\": true, \"key2\": false}".into()).unwrap();
-        assert_eq!(tokens.len(), 14);
-        assert_eq!(Tokens::LeftBrace, tokens[0]);
-        assert_eq!(Tokens::DoubleQuote, tokens[1]);
-        assert_eq!(Tokens::StringValue("key".into()), tokens[2]);
-        assert_eq!(Tokens::DoubleQuote, tokens[3]);
-        assert_eq!(Tokens::Colon, tokens[4]);
-        assert_eq!(Tokens::BooleanValue(true), tokens[5]);
-        assert_eq!(Tokens::Comma, tokens[6]);
-        assert_eq!(Tokens::DoubleQuote, tokens[7]);
-        assert_eq!(Tokens::StringValue("key2".into()), tokens[8]);
-        assert_eq!(Tokens::DoubleQuote, tokens[9]);
-        assert_eq!(Tokens::Colon, tokens[10]);
-        assert_eq!(Tokens::BooleanValue(false), tokens[11]);
-        assert_eq!(Tokens::RightBrace, tokens[12]);
-    }
-
-    #[test]
-    fn test_tokenize_integer_values() {
-        let tokens = tokenize("{\"key\": -32, \"key2\": 14}".into()).unwrap();
-        assert_eq!(tokens.len(), 14);
-        assert_eq!(Tokens::LeftBrace, tokens[0]);
-        assert_eq!(Tokens::DoubleQuote, tokens[1]);
-        assert_eq!(Tokens::StringValue("key".into()), tokens[2]);
-        assert_eq!(Tokens::DoubleQuote, tokens[3]);
-        assert_eq!(Tokens::Colon, tokens[4]);
-        assert_eq!(Tokens::IntegerValue(-32), tokens[5]);
-        assert_eq!(Tokens::Comma, tokens[6]);
-        assert_eq!(Tokens::DoubleQuote, tokens[7]);
-        assert_eq!(Tokens::StringValue("key2".into()), tokens[8]);
-        assert_eq!(Tokens::DoubleQuote, tokens[9]);
-        assert_eq!(Tokens::Colon, tokens[10]);
-        assert_eq!(Tokens::IntegerValue(14), tokens[11]);
-        assert_eq!(Tokens::RightBrace, tokens[12]);
-    }
-
-    #[test]
-    fn test_tokenize_float_values() {
-        let tokens = tokenize("{\"key\": -3.2, \"key2\": 0.14}".into()).unwrap();
-        assert_eq!(tokens.len(), 14);
-        assert_eq!(Tokens::LeftBrace, tokens[0]);
-        assert_eq!(Tokens::DoubleQuote, tokens[1]);
-        assert_eq!(Tokens::StringValue("key".into()), tokens[2]);
-        assert_eq!(Tokens::DoubleQuote, tokens[3]);
-        assert_eq!(Tokens::Colon, tokens[4]);
-        assert_eq!(Tokens::FloatValue(-3.2), tokens[5]);
-        assert_eq!(Tokens::Comma, tokens[6]);
-        assert_eq!(Tokens::DoubleQuote, tokens[7]);
-        assert_eq!(Tokens::StringValue("key2".into()), tokens[8]);
-        assert_eq!(Tokens::DoubleQuote, tokens[9]);
-        assert_eq!(Tokens::Colon, tokens[10]);
-        assert_eq!(Tokens::FloatValue(0.14), tokens[11]);
-        assert_eq!(Tokens::RightBrace, tokens[12]);
-    }
-
-    #[test]
-    fn test_tokenize_float_scientific_notation_values() {
-        let tokens = tokenize("{\"key\": -3E3, \"key2\": 14E-4}".into()).unwrap();
-        //not parsing negative exponent e-1
-        assert_eq!(tokens.len(), 14);
-        assert_eq!(Tokens::LeftBrace, tokens[0]);
-        assert_eq!(Tokens::DoubleQuote, tokens[1]);
-        assert_eq!(Tokens::StringValue("key".into()), tokens[2]);
-        assert_eq!(Tokens::DoubleQuote, tokens[3]);
-        assert_eq!(Tokens::Colon, tokens[4]);
-        assert_eq!(Tokens::FloatValue(-3000.0), tokens[5]);
-        assert_eq!(Tokens::Comma, tokens[6]);
-        assert_eq!(Tokens::DoubleQuote, tokens[7]);
-        assert_eq!(Tokens::StringValue("key2".into()), tokens[8]);
-        assert_eq!(Tokens::DoubleQuote, tokens[9]);
-        assert_eq!(Tokens::Colon, tokens[10]);
-        assert_eq!(Tokens::FloatValue(0.0014), tokens[11]);
-        assert_eq!(Tokens::RightBrace, tokens[12]);
-    }
-
-    #[test]
-    fn test_tokenize_null_values() {
-        let tokens = tokenize("{\"key\": null, \"key2\": null}".into()).unwrap();
-        assert_eq!(tokens.len(), 14);
-        assert_eq!(Tokens::LeftBrace, tokens[0]);
-        assert_eq!(Tokens::DoubleQuote, tokens[1]);
-        assert_eq!(Tokens::StringValue("key".into()), tokens[2]);
-        assert_eq!(Tokens::DoubleQuote, tokens[3]);
-        assert_eq!(Tokens::Colon, tokens[4]);
-        assert_eq!(Tokens::NullValue, tokens[5]);
-        assert_eq!(Tokens::Comma, tokens[6]);
-        assert_eq!(Tokens::DoubleQuote, tokens[7]);
-        assert_eq!(Tokens::StringValue("key2".into()), tokens[8]);
-        assert_eq!(Tokens::DoubleQuote, tokens[9]);
-        assert_eq!(Tokens::Colon, tokens[10]);
-        assert_eq!(Tokens::NullValue, tokens[11]);
-        assert_eq!(Tokens::RightBrace, tokens[12]);
-    }
-
-    #[test]
-    fn test_tokenize_on_brackets() {
-        let tokens = tokenize("[]".into()).unwrap();
-
-        assert_eq!(tokens.len(), 3);
-        assert_eq!(Tokens::LeftBracket, tokens[0]);
-        assert_eq!(Tokens::RightBracket, tokens[1]);
-    }
-
-    #[test]
-    fn test_tokenize_list() {
-        let tokens = tokenize("[\"one\", 2, true]".into()).unwrap();
-
-        assert_eq!(tokens.len(), 10);
-        assert_eq!(Tokens::LeftBracket, tokens[0]);
-        assert_eq!(Tokens::DoubleQuote, tokens[1]);
-        assert_eq!(Tokens::StringValue("one".into()), tokens[2]);
-        assert_eq!(Tokens::DoubleQuote, tokens[3]);
-        assert_eq!(Tokens::Comma, tokens[4]);
-        assert_eq!(Tokens::IntegerValue(2), tokens[5]);
-        assert_eq!(Tokens::Comma, tokens[6]);
-        assert_eq!(Tokens::BooleanValue(true), tokens[7]);
-        assert_eq!(Tokens::RightBracket, tokens[8]);
-    }
-
-    #[test]
-    fn test_tokenize_list_nested_object() {
-        let tokens = tokenize("[\"one\", 2, { \"inner key\": true}]".into()).unwrap();
-
-        assert_eq!(tokens.len(), 16);
-        assert_eq!(Tokens::LeftBracket, tokens[0]);
-        assert_eq!(Tokens::DoubleQuote, tokens[1]);
-        assert_eq!(Tokens::StringValue("one".into()), tokens[2]);
-        assert_eq!(Tokens::DoubleQuote, tokens[3]);
-        assert_eq!(Tokens::Comma, tokens[4]);
-        assert_eq!(Tokens::IntegerValue(2), tokens[5]);
-        assert_eq!(Tokens::Comma, tokens[6]);
-
-        assert_eq!(Tokens::LeftBrace, tokens[7]);
-        assert_eq!(Tokens::DoubleQuote, tokens[8]);
-        assert_eq!(Tokens::StringValue("inner key".into()), tokens[9]);
-        assert_eq!(Tokens::DoubleQuote, tokens[10]);
-        assert_eq!(Tokens::Colon, tokens[11]);
-        assert_eq!(Tokens::BooleanValue(true), tokens[12]);
-        assert_eq!(Tokens::RightBrace, tokens[13]);
-        assert_eq!(Tokens::RightBracket, tokens[14]);
-    }
-
-    #[test]
-    fn test_tokenize_quote() {
-        let input: String = "{\"key\": \"\"\"}".into();
-
-        let tokens = tokenize(input).unwrap();
-
-        assert_eq!(tokens.len(), 10);
-        assert_eq!(Tokens::LeftBrace, tokens[0]);
-        assert_eq!(Tokens::DoubleQuote, tokens[1]);
-        assert_eq!(Tokens::StringValue("key".into()), tokens[2]);
-        assert_eq!(Tokens::DoubleQuote, tokens[3]);
-        assert_eq!(Tokens::Colon, tokens[4]);
-        assert_eq!(Tokens::DoubleQuote, tokens[5]);
-        assert_eq!(Tokens::StringValue("\"".into()), tokens[6]);
-        assert_eq!(Tokens::DoubleQuote, tokens[7]);
-        assert_eq!(Tokens::RightBrace, tokens[8]);
+    //gives hand written tokens an empty span so they can be parsed
+    fn spanned(tokens: Vec<Tokens>) -> impl Iterator<Item = Result<Token, ParserErrors>> {
+        tokens.into_iter().map(|kind| {
+            Ok(Token {
+                kind,
+                span: Span::default(),
+            })
+        })
     }
 
     //Parsing tests
@@ -548,26 +807,26 @@ mod tests {
     #[test]
     fn test_parse_works_on_single_braces_document() {
         let tokens: Vec<Tokens> = vec![Tokens::LeftBrace, Tokens::RightBrace];
-        let json_document = &mut JSONDocument {};
-        let valid = json_document.parse(tokens).unwrap();
+        let json_document = &mut JSONDocument::default();
+        let value = json_document.parse(spanned(tokens), build_value).unwrap();
 
-        assert!(valid);
+        assert_eq!(value, JsonValue::Object(vec![].into()));
     }
 
     #[test]
     fn test_parse_fails_on_invalid_tokens() {
         let tokens: Vec<Tokens> = vec![Tokens::RightBrace, Tokens::RightBrace];
 
-        let json_document = &mut JSONDocument {};
+        let json_document = &mut JSONDocument::default();
 
-        assert!(json_document.parse(tokens).is_err());
+        assert!(json_document.parse(spanned(tokens), build_value).is_err());
     }
 
     #[test]
     fn test_parse_fails_on_short_tokens() {
         let tokens: Vec<Tokens> = Vec::new();
-        let json_document = &mut JSONDocument {};
-        assert!(json_document.parse(tokens).is_err());
+        let json_document = &mut JSONDocument::default();
+        assert!(json_document.parse(spanned(tokens), build_value).is_err());
     }
 
     #[test]
@@ -585,9 +844,12 @@ mod tests {
             Tokens::EOF,
         ];
 
-        let json_document = &mut JSONDocument {};
-        let valid = json_document.parse(tokens).unwrap();
-        assert!(valid);
+        let json_document = &mut JSONDocument::default();
+        let value = json_document.parse(spanned(tokens), build_value).unwrap();
+        assert_eq!(
+            value,
+            JsonValue::Object(vec![("key".into(), JsonValue::String("value".into()))].into())
+        );
     }
 
     #[test]
@@ -606,14 +868,14 @@ mod tests {
             Tokens::StringValue("key".into()),
             Tokens::DoubleQuote,
             Tokens::Colon,
-            Tokens::IntegerValue(42),
+            Tokens::NumberValue("42".into()),
             Tokens::Comma,
             //"key":3.2,
             Tokens::DoubleQuote,
             Tokens::StringValue("key".into()),
             Tokens::DoubleQuote,
             Tokens::Colon,
-            Tokens::FloatValue(-3.2),
+            Tokens::NumberValue("-3.2".into()),
             Tokens::Comma,
             //"key":null}
             Tokens::DoubleQuote,
@@ -625,17 +887,28 @@ mod tests {
             Tokens::EOF,
         ];
 
-        let json_document = &mut JSONDocument {};
-        let valid = json_document.parse(tokens).unwrap();
-        assert!(valid);
+        let json_document = &mut JSONDocument::default();
+        let value = json_document.parse(spanned(tokens), build_value).unwrap();
+        assert_eq!(
+            value,
+            JsonValue::Object(
+                vec![
+                    ("key".into(), JsonValue::Bool(true)),
+                    ("key".into(), JsonValue::Number(Number::PosInt(42))),
+                    ("key".into(), JsonValue::Number(Number::Float(-3.2))),
+                    ("key".into(), JsonValue::Null),
+                ]
+                .into()
+            )
+        );
     }
     #[test]
     fn test_parse_works_on_single_brackets_document() {
         let tokens: Vec<Tokens> = vec![Tokens::LeftBracket, Tokens::RightBracket, Tokens::EOF];
-        let json_document = &mut JSONDocument {};
-        let valid = json_document.parse(tokens).unwrap();
+        let json_document = &mut JSONDocument::default();
+        let value = json_document.parse(spanned(tokens), build_value).unwrap();
 
-        assert!(valid);
+        assert_eq!(value, JsonValue::Array(vec![].into()));
     }
 
     #[test]
@@ -646,14 +919,23 @@ mod tests {
             Tokens::StringValue("one".into()),
             Tokens::DoubleQuote,
             Tokens::Comma,
-            Tokens::IntegerValue(42),
+            Tokens::NumberValue("42".into()),
             Tokens::RightBracket,
             Tokens::EOF,
         ];
-        let json_document = &mut JSONDocument {};
-        let valid = json_document.parse(tokens).unwrap();
+        let json_document = &mut JSONDocument::default();
+        let value = json_document.parse(spanned(tokens), build_value).unwrap();
 
-        assert!(valid);
+        assert_eq!(
+            value,
+            JsonValue::Array(
+                vec![
+                    JsonValue::String("one".into()),
+                    JsonValue::Number(Number::PosInt(42)),
+                ]
+                .into()
+            )
+        );
     }
 
     #[test]
@@ -672,7 +954,7 @@ mod tests {
             Tokens::StringValue("key-n".into()),
             Tokens::DoubleQuote,
             Tokens::Colon,
-            Tokens::IntegerValue(101),
+            Tokens::NumberValue("101".into()),
             Tokens::Comma,
             Tokens::DoubleQuote,
             Tokens::StringValue("key-o".into()),
@@ -691,9 +973,20 @@ mod tests {
             Tokens::EOF,
         ];
 
-        let json_document = &mut JSONDocument {};
-        let valid = json_document.parse(tokens).unwrap();
-        assert!(valid);
+        let json_document = &mut JSONDocument::default();
+        let value = json_document.parse(spanned(tokens), build_value).unwrap();
+        assert_eq!(
+            value,
+            JsonValue::Object(
+                vec![
+                    ("key".into(), JsonValue::String("value".into())),
+                    ("key-n".into(), JsonValue::Number(Number::PosInt(101))),
+                    ("key-o".into(), JsonValue::Object(vec![].into())),
+                    ("key-l".into(), JsonValue::Array(vec![].into())),
+                ]
+                .into()
+            )
+        );
     }
 
     #[test]
@@ -712,7 +1005,7 @@ mod tests {
             Tokens::StringValue("key-n".into()),
             Tokens::DoubleQuote,
             Tokens::Colon,
-            Tokens::IntegerValue(101),
+            Tokens::NumberValue("101".into()),
             Tokens::Comma,
             Tokens::DoubleQuote,
             Tokens::StringValue("key-o".into()),
@@ -741,8 +1034,555 @@ mod tests {
             Tokens::EOF,
         ];
 
-        let json_document = &mut JSONDocument {};
-        let valid = json_document.parse(tokens).unwrap();
-        assert!(valid);
+        let json_document = &mut JSONDocument::default();
+        let value = json_document.parse(spanned(tokens), build_value).unwrap();
+        assert_eq!(
+            value,
+            JsonValue::Object(
+                vec![
+                    ("key".into(), JsonValue::String("value".into())),
+                    ("key-n".into(), JsonValue::Number(Number::PosInt(101))),
+                    (
+                        "key-o".into(),
+                        JsonValue::Object(
+                            vec![("inner key".into(), JsonValue::String("inner value".into()))]
+                                .into()
+                        )
+                    ),
+                    (
+                        "key-l".into(),
+                        JsonValue::Array(vec![JsonValue::String("list value".into())].into())
+                    ),
+                ]
+                .into()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_nested_lists() {
+        //[[1], [], [true, [null]]]
+        let tokens = vec![
+            Tokens::LeftBracket,
+            Tokens::LeftBracket,
+            Tokens::NumberValue("1".into()),
+            Tokens::RightBracket,
+            Tokens::Comma,
+            Tokens::LeftBracket,
+            Tokens::RightBracket,
+            Tokens::Comma,
+            Tokens::LeftBracket,
+            Tokens::BooleanValue(true),
+            Tokens::Comma,
+            Tokens::LeftBracket,
+            Tokens::NullValue,
+            Tokens::RightBracket,
+            Tokens::RightBracket,
+            Tokens::RightBracket,
+            Tokens::EOF,
+        ];
+
+        let json_document = &mut JSONDocument::default();
+        let value = json_document.parse(spanned(tokens), build_value).unwrap();
+        assert_eq!(
+            value,
+            JsonValue::Array(
+                vec![
+                    JsonValue::Array(vec![JsonValue::Number(Number::PosInt(1))].into()),
+                    JsonValue::Array(vec![].into()),
+                    JsonValue::Array(
+                        vec![
+                            JsonValue::Bool(true),
+                            JsonValue::Array(vec![JsonValue::Null].into()),
+                        ]
+                        .into()
+                    ),
+                ]
+                .into()
+            )
+        );
+    }
+
+    //public api tests
+    //------------------
+    #[test]
+    fn test_from_str_returns_document() {
+        let value = from_str("{\"key\": [\"one\", 2, null]}").unwrap();
+        assert_eq!(
+            value,
+            JsonValue::Object(
+                vec![(
+                    "key".into(),
+                    JsonValue::Array(
+                        vec![
+                            JsonValue::String("one".into()),
+                            JsonValue::Number(Number::PosInt(2)),
+                            JsonValue::Null,
+                        ]
+                        .into()
+                    )
+                )]
+                .into()
+            )
+        );
+    }
+
+    #[test]
+    fn test_from_str_fails_on_invalid_document() {
+        assert!(matches!(
+            from_str("{\"key\" \"value\"}"),
+            Err(ParserErrors::ParsingError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let error = from_str("{\n  \"key\", 1\n}").unwrap_err();
+        assert_eq!(
+            error.position(),
+            Some(Position {
+                offset: 9,
+                line: 2,
+                column: 8
+            })
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid json: expected ':' after object key at line 2, column 8 (byte 9)"
+        );
+    }
+
+    #[test]
+    fn test_parse_scalar_documents() {
+        assert_eq!(
+            from_str("\"bare string\"").unwrap(),
+            JsonValue::String("bare string".into())
+        );
+        assert_eq!(
+            from_str(" -12.5e1 ").unwrap(),
+            JsonValue::Number(Number::Float(-125.0))
+        );
+        assert_eq!(from_str("true").unwrap(), JsonValue::Bool(true));
+        assert_eq!(from_str("null\n").unwrap(), JsonValue::Null);
+        assert!(from_str("1 2").is_err());
+        assert!(from_str("").is_err());
+        assert!(from_str(":").is_err());
+    }
+
+    //checks parsing `input` fails with a ParsingError saying `message` at byte
+    //`offset`
+    #[track_caller]
+    fn assert_parse_error(input: &str, message: &str, offset: usize) {
+        assert_parse_error_with(&JSONDocument::default(), input, message, offset)
+    }
+
+    #[track_caller]
+    fn assert_parse_error_with(
+        json_document: &JSONDocument,
+        input: &str,
+        message: &str,
+        offset: usize,
+    ) {
+        match json_document.parse_str(input) {
+            Err(ParserErrors::ParsingError {
+                message: found,
+                position,
+            }) => {
+                assert_eq!(found, message, "{input}");
+                assert_eq!(position.offset, offset, "{input}");
+            }
+            other => panic!("{input} gave {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_scalar_documents_rfc4627() {
+        let json_document = JSONDocument::new(ParserOptions {
+            allow_scalar_root: false,
+            ..Default::default()
+        });
+        for input in ["\"bare string\"", "42", "false", "null"] {
+            assert_parse_error_with(
+                &json_document,
+                input,
+                "expected '{' or '[' at the start of the document",
+                0,
+            );
+        }
+        assert!(json_document.parse_str("[42]").is_ok());
+    }
+
+    #[test]
+    fn test_parse_rejects_misplaced_commas() {
+        for (input, message, offset) in [
+            ("[1,]", "trailing comma before ']'", 3),
+            ("[1,,2]", "unexpected ','", 3),
+            ("[,1]", "unexpected ','", 1),
+            ("{\"a\":1,}", "trailing comma before '}'", 7),
+            ("{,\"a\":1}", "unexpected ','", 1),
+            ("{\"a\":1,,\"b\":2}", "unexpected ','", 7),
+        ] {
+            assert_parse_error(input, message, offset);
+        }
+    }
+
+    #[test]
+    fn test_parse_requires_separators() {
+        for (input, message, offset) in [
+            ("[1 2]", "expected ',' or ']' after list element", 3),
+            ("[[] {}]", "expected ',' or ']' after list element", 4),
+            (
+                "[\"a\": false]",
+                "expected ',' or ']' after list element",
+                4,
+            ),
+            (
+                "{\"a\":1 \"b\":2}",
+                "expected ',' or '}' after object member",
+                7,
+            ),
+            ("{\"a\":1:2}", "expected ',' or '}' after object member", 6),
+            ("{\"a\" 1}", "expected ':' after object key", 5),
+            ("{\"a\", 1}", "expected ':' after object key", 4),
+            ("{\"a\"::1}", "expected a value", 5),
+            ("{\"a\":}", "expected a value", 5),
+            ("{1:2}", "expected a quoted object key", 1),
+            ("{\"a\":1, 2:3}", "expected a quoted object key", 8),
+        ] {
+            assert_parse_error(input, message, offset);
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_unclosed_documents() {
+        for (input, message, offset) in [
+            ("[\"Unclosed array\"", "expected ']' to close the list", 17),
+            ("{\"a\": true,", "expected '}' to close the object", 11),
+            ("[[]", "expected ']' to close the list", 3),
+        ] {
+            assert_parse_error(input, message, offset);
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_content_after_document() {
+        for (input, offset) in [
+            ("{}}", 2),
+            ("[1] [2]", 4),
+            ("{\"a\": 1} \"b\"", 9),
+            ("[],", 2),
+        ] {
+            assert_parse_error(input, "unexpected content after the document", offset);
+        }
+        assert!(from_str("[1]  \n").is_ok());
+    }
+
+    #[test]
+    fn test_parse_depth_limit() {
+        let json_document = JSONDocument::new(ParserOptions {
+            max_depth: Some(3),
+            ..Default::default()
+        });
+        assert!(json_document.parse_str("[{\"a\": [1]}, [[]]]").is_ok());
+        match json_document.parse_str("[{\"a\": [[1]]}]") {
+            Err(ParserErrors::DepthLimitExceeded { depth, position }) => {
+                assert_eq!(depth, 4);
+                assert_eq!(position.offset, 8);
+            }
+            other => panic!("got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_hostile_nesting_fails_cleanly() {
+        let input = "[".repeat(100_000);
+        assert!(matches!(
+            from_str(&input),
+            Err(ParserErrors::DepthLimitExceeded { depth: 129, .. })
+        ));
+        let input = "{\"a\":".repeat(100_000);
+        assert!(matches!(
+            from_str(&input),
+            Err(ParserErrors::DepthLimitExceeded { depth: 129, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_deep_nesting_without_limit() {
+        let json_document = JSONDocument::new(ParserOptions {
+            max_depth: None,
+            ..Default::default()
+        });
+        let depth = 200_000;
+        let input = format!("{}{{}}{}", "[{\"a\":".repeat(depth), "}]".repeat(depth));
+        let mut value = &json_document.parse_str(&input).unwrap();
+        let mut levels = 0;
+        while let Some(inner) = value.as_array().and_then(|elements| elements.first()) {
+            value = inner.get("a").unwrap();
+            levels += 1;
+        }
+        assert_eq!(levels, depth);
+        assert_eq!(value, &JsonValue::Object(vec![].into()));
+
+        //still reports errors where they are
+        let input = format!("{}1,]{}", "[".repeat(depth), "]".repeat(depth - 1));
+        assert_parse_error_with(
+            &json_document,
+            &input,
+            "trailing comma before ']'",
+            depth + 2,
+        );
+    }
+
+    #[test]
+    fn test_parse_str_stream() {
+        let json_document = JSONDocument::default();
+        let documents = json_document
+            .parse_str_stream("[1] [2]\n{\"a\": null}{}\n\"three\" 4\n")
+            .unwrap();
+        assert_eq!(
+            documents,
+            vec![
+                JsonValue::Array(vec![JsonValue::Number(Number::PosInt(1))].into()),
+                JsonValue::Array(vec![JsonValue::Number(Number::PosInt(2))].into()),
+                JsonValue::Object(vec![("a".into(), JsonValue::Null)].into()),
+                JsonValue::Object(vec![].into()),
+                JsonValue::String("three".into()),
+                JsonValue::Number(Number::PosInt(4)),
+            ]
+        );
+        assert_eq!(json_document.parse_str_stream(" \n").unwrap(), vec![]);
+        //every document still has to be complete and well formed
+        assert!(json_document.parse_str_stream("[1] [2").is_err());
+        assert!(json_document.parse_str_stream("[1] ]").is_err());
+        assert!(json_document.parse_str_stream("[1], [2]").is_err());
+    }
+
+    #[test]
+    fn test_stream_reader() {
+        let json_document = JSONDocument::default();
+        let documents: Vec<_> = json_document
+            .stream_reader("{\"id\": 1}\n{\"id\": 2}\n".as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(
+            documents[1].get("id"),
+            Some(&JsonValue::Number(Number::PosInt(2)))
+        );
+
+        //the documents before an error are still handed out, nothing after it
+        let mut documents = json_document.stream_reader("[1] [2,] [3]".as_bytes());
+        assert!(matches!(documents.next(), Some(Ok(_))));
+        assert!(matches!(
+            documents.next(),
+            Some(Err(ParserErrors::ParsingError { .. }))
+        ));
+        assert!(documents.next().is_none());
+    }
+
+    #[test]
+    fn test_parse_numbers_keep_precision() {
+        let value = from_str("[9007199254740993, -9223372036854775808, 0.1, 1e400]");
+        assert!(matches!(
+            value,
+            Err(ParserErrors::InvalidNumber {
+                reason: "number out of range",
+                ..
+            })
+        ));
+
+        let value = from_str("[9007199254740993, -9223372036854775808, 0.1]").unwrap();
+        assert_eq!(
+            value,
+            JsonValue::Array(
+                vec![
+                    JsonValue::Number(Number::PosInt(9007199254740993)),
+                    JsonValue::Number(Number::NegInt(i64::MIN)),
+                    JsonValue::Number(Number::Float(0.1)),
+                ]
+                .into()
+            )
+        );
+
+        let json_document = JSONDocument::new(ParserOptions {
+            arbitrary_precision: true,
+            ..Default::default()
+        });
+        let value = json_document
+            .parse_str("[123456789012345678901234567890, 1e400]")
+            .unwrap();
+        assert_eq!(
+            value,
+            JsonValue::Array(
+                vec![
+                    JsonValue::Number(Number::Raw("123456789012345678901234567890".into())),
+                    JsonValue::Number(Number::Raw("1e400".into())),
+                ]
+                .into()
+            )
+        );
+    }
+
+    #[test]
+    fn test_from_slice_rejects_invalid_utf8() {
+        let error = from_slice(b"[\"\xff\"]").unwrap_err();
+        assert!(matches!(error, ParserErrors::InvalidUtf8 { .. }));
+        assert_eq!(error.position().unwrap().offset, 2);
+        assert_eq!(from_slice(b"[]").unwrap(), JsonValue::Array(vec![].into()));
+    }
+
+    #[test]
+    fn test_from_reader_reads_whole_input() {
+        let reader = std::io::Cursor::new("[true, false]");
+        assert_eq!(
+            from_reader(reader).unwrap(),
+            JsonValue::Array(vec![JsonValue::Bool(true), JsonValue::Bool(false)].into())
+        );
+    }
+
+    //a list of `count` zeros written out as it is read
+    struct Zeros {
+        count: usize,
+        written: usize,
+    }
+    impl Read for Zeros {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            let mut filled = 0;
+            for byte in buffer.iter_mut() {
+                *byte = match self.written {
+                    0 => b'[',
+                    n if n == self.count * 2 => b']',
+                    n if n > self.count * 2 => break,
+                    n if n % 2 == 1 => b'0',
+                    _ => b',',
+                };
+                self.written += 1;
+                filled += 1;
+            }
+            Ok(filled)
+        }
+    }
+
+    #[test]
+    fn test_validate_reader_streams_the_input() {
+        let json_document = JSONDocument::default();
+        let zeros = Zeros {
+            count: 500_000,
+            written: 0,
+        };
+        assert!(json_document.validate_reader(zeros).is_ok());
+
+        let error = json_document
+            .validate_reader("[[1], {\"key\": 2,}]".as_bytes())
+            .unwrap_err();
+        assert_eq!(error.position().unwrap().offset, 16);
+        assert!(json_document.validate_reader("[1] 2".as_bytes()).is_err());
+        assert!(json_document.validate_reader("  ".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_events_str() {
+        let json_document = JSONDocument::default();
+        let events: Vec<(Event, Span)> = json_document
+            .events_str("{\"a\": [1, \"b\", null]}")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let kinds: Vec<Event> = events.iter().map(|(event, _)| event.clone()).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                Event::StartObject,
+                Event::Key("a".into()),
+                Event::StartArray,
+                Event::Number(Number::PosInt(1)),
+                Event::String("b".into()),
+                Event::Null,
+                Event::EndArray,
+                Event::EndObject,
+            ]
+        );
+        //keys and strings span their quotes
+        assert_eq!((events[1].1.start.offset, events[1].1.end.offset), (1, 4));
+        assert_eq!((events[4].1.start.offset, events[4].1.end.offset), (10, 13));
+        assert_eq!(events[7].1.start.offset, 20);
+    }
+
+    #[test]
+    fn test_events_end_with_the_first_error() {
+        let json_document = JSONDocument::default();
+
+        let mut events = json_document.events_str("[1] [2]");
+        assert_eq!(events.next().unwrap().unwrap().0, Event::StartArray);
+        assert_eq!(
+            events.next().unwrap().unwrap().0,
+            Event::Number(Number::PosInt(1))
+        );
+        assert_eq!(events.next().unwrap().unwrap().0, Event::EndArray);
+        assert!(events.next().unwrap().is_err());
+        assert!(events.next().is_none());
+
+        let mut events = json_document.events_reader("[1 2]".as_bytes());
+        assert!(events.next().unwrap().is_ok());
+        assert!(events.next().unwrap().is_ok());
+        assert!(events.next().unwrap().is_err());
+        assert!(events.next().is_none());
+
+        assert!(json_document.events_str("").next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_events_scalar_document() {
+        let events: Vec<Event> = JSONDocument::default()
+            .events_str(" true ")
+            .map(|event| event.unwrap().0)
+            .collect();
+        assert_eq!(events, vec![Event::Bool(true)]);
+
+        let strict = JSONDocument::new(ParserOptions {
+            allow_scalar_root: false,
+            ..Default::default()
+        });
+        assert!(strict.events_str("true").next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_parse_str_borrows_unescaped_strings() {
+        let input = r#"{"plain": "text", "esc\u0061ped": ["a\nb", "c"]}"#;
+        let value = from_str(input).unwrap();
+        let members = value.as_object().unwrap();
+
+        assert!(matches!(members[0].0, Cow::Borrowed("plain")));
+        assert!(matches!(
+            value.get("plain"),
+            Some(JsonValue::String(Cow::Borrowed("text")))
+        ));
+        assert!(matches!(&members[1].0, Cow::Owned(key) if key == "escaped"));
+        let elements = value.get("escaped").unwrap().as_array().unwrap();
+        assert!(matches!(&elements[0], JsonValue::String(Cow::Owned(string)) if string == "a\nb"));
+        assert!(matches!(elements[1], JsonValue::String(Cow::Borrowed("c"))));
+
+        //readers have nothing to borrow from
+        let value = from_reader(input.as_bytes()).unwrap();
+        assert!(matches!(value.as_object().unwrap()[0].0, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_parse_allow_bom() {
+        let input = b"\xef\xbb\xbf{\"key\": 1}";
+        assert!(from_slice(input).is_err());
+
+        let json_document = JSONDocument::new(ParserOptions {
+            allow_bom: true,
+            ..Default::default()
+        });
+        let value = json_document.parse_slice(input).unwrap();
+        assert_eq!(
+            value.get("key"),
+            Some(&JsonValue::Number(Number::PosInt(1)))
+        );
+        assert!(json_document.validate_reader(&input[..]).is_ok());
+        assert!(json_document.parse_str("\u{feff}[]").is_ok());
     }
 }
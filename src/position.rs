@@ -0,0 +1,191 @@
+use std::{
+    fmt,
+    io::{self, BufRead},
+};
+
+use crate::ParserErrors;
+
+/// A location in the input: the byte offset plus the 1-based line and column.
+///
+/// Columns count characters, not bytes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {} (byte {})",
+            self.line, self.column, self.offset
+        )
+    }
+}
+
+/// The part of the input a token was read from, `end` being exclusive.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+}
+
+//decodes the input char by char keeping track of where the next char starts.
+//Bytes are pulled from the reader as they are needed, so only its buffer is
+//ever held in memory
+pub(crate) struct Chars<R> {
+    reader: R,
+    //decoded by peek but not consumed yet
+    peeked: Option<char>,
+    position: Position,
+}
+
+impl<R: BufRead> Chars<R> {
+    pub(crate) fn new(reader: R) -> Chars<R> {
+        Chars {
+            reader,
+            peeked: None,
+            position: Position::default(),
+        }
+    }
+
+    //returns the next char together with the position it starts at
+    pub(crate) fn next(&mut self) -> Result<Option<(Position, char)>, ParserErrors> {
+        let c = match self.peeked.take() {
+            Some(c) => c,
+            None => match self.decode()? {
+                Some(c) => c,
+                None => return Ok(None),
+            },
+        };
+        let start = self.position;
+        self.position.offset += c.len_utf8();
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        Ok(Some((start, c)))
+    }
+
+    pub(crate) fn peek(&mut self) -> Result<Option<char>, ParserErrors> {
+        if self.peeked.is_none() {
+            self.peeked = self.decode()?;
+        }
+        Ok(self.peeked)
+    }
+
+    //position of the next char, or the end of the input once consumed
+    pub(crate) fn position(&self) -> Position {
+        self.position
+    }
+
+    //reads the one to four bytes of the next char
+    fn decode(&mut self) -> Result<Option<char>, ParserErrors> {
+        let Some(first) = self.read_byte()? else {
+            return Ok(None);
+        };
+        let width = match first {
+            0x00..=0x7F => return Ok(Some(first as char)),
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            //a stray continuation byte, left for from_utf8 to reject
+            _ => 1,
+        };
+        let mut bytes = [first, 0, 0, 0];
+        let mut read = 1;
+        while read < width {
+            let Some(byte) = self.read_byte()? else {
+                break;
+            };
+            bytes[read] = byte;
+            read += 1;
+        }
+        match std::str::from_utf8(&bytes[..read]) {
+            Ok(decoded) => Ok(decoded.chars().next()),
+            //overlong encodings, surrogates and bad continuation bytes alike
+            Err(_) => Err(ParserErrors::InvalidUtf8 {
+                position: self.position,
+            }),
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, ParserErrors> {
+        let buffer = loop {
+            match self.reader.fill_buf() {
+                Ok(buffer) => break buffer,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into()),
+            }
+        };
+        let Some(&byte) = buffer.first() else {
+            return Ok(None);
+        };
+        self.reader.consume(1);
+        Ok(Some(byte))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chars_tracks_lines_and_columns() {
+        let mut chars = Chars::new("a\nбc".as_bytes());
+
+        assert_eq!(chars.next().unwrap().unwrap().0, Position::default());
+        assert_eq!(
+            chars.next().unwrap().unwrap(),
+            (
+                Position {
+                    offset: 1,
+                    line: 1,
+                    column: 2
+                },
+                '\n'
+            )
+        );
+        chars.next().unwrap();
+        assert_eq!(
+            chars.next().unwrap().unwrap(),
+            (
+                Position {
+                    offset: 4,
+                    line: 2,
+                    column: 2
+                },
+                'c'
+            )
+        );
+        assert!(chars.next().unwrap().is_none());
+        assert_eq!(
+            chars.position(),
+            Position {
+                offset: 5,
+                line: 2,
+                column: 3
+            }
+        );
+    }
+}
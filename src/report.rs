@@ -0,0 +1,273 @@
+use std::{borrow::Cow, fmt::Write, path::Path};
+
+use crate::{cli::Diagnostic, diagnostic, JsonValue, Number, ParserErrors};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+//the outcome of validating one file, as it goes into a report
+pub(crate) struct Outcome<'r> {
+    pub(crate) path: &'r str,
+    pub(crate) result: &'r Result<(), Diagnostic>,
+}
+
+//one line of `--output-format json`:
+//{"path":"a.json","valid":false,"kind":"parsing_error","line":1,"column":4,"message":"..."}
+//with the error fields null for valid files
+pub(crate) fn json_record(outcome: &Outcome) -> JsonValue<'static> {
+    let (kind, line, column, message) = match outcome.result {
+        Ok(()) => (
+            JsonValue::Null,
+            JsonValue::Null,
+            JsonValue::Null,
+            JsonValue::Null,
+        ),
+        Err(Diagnostic { error, .. }) => {
+            let (line, column) = match error.position() {
+                Some(position) => (number(position.line), number(position.column)),
+                None => (JsonValue::Null, JsonValue::Null),
+            };
+            (string(kind(error)), line, column, string(message(error)))
+        }
+    };
+    object(vec![
+        ("path", string(outcome.path)),
+        ("valid", JsonValue::Bool(outcome.result.is_ok())),
+        ("kind", kind),
+        ("line", line),
+        ("column", column),
+        ("message", message),
+    ])
+}
+
+//a SARIF 2.1.0 log with one result per invalid file, each kind of error
+//being a rule
+pub(crate) fn sarif_log(outcomes: &[Outcome]) -> JsonValue<'static> {
+    let mut kinds: Vec<&'static str> = Vec::new();
+    let mut rules = Vec::new();
+    let mut results = Vec::new();
+    for outcome in outcomes {
+        let Err(Diagnostic { error, .. }) = outcome.result else {
+            continue;
+        };
+        if !kinds.contains(&kind(error)) {
+            kinds.push(kind(error));
+            rules.push(object(vec![
+                ("id", string(kind(error))),
+                (
+                    "shortDescription",
+                    object(vec![("text", string(diagnostic::headline(error)))]),
+                ),
+            ]));
+        }
+
+        let mut location = vec![("artifactLocation", artifact_location(outcome.path))];
+        if let Some(position) = error.position() {
+            location.push((
+                "region",
+                object(vec![
+                    ("startLine", number(position.line)),
+                    ("startColumn", number(position.column)),
+                ]),
+            ));
+        }
+        results.push(object(vec![
+            ("ruleId", string(kind(error))),
+            ("level", string("error")),
+            ("message", object(vec![("text", string(message(error)))])),
+            (
+                "locations",
+                JsonValue::Array(vec![object(vec![("physicalLocation", object(location))])].into()),
+            ),
+        ]));
+    }
+
+    let driver = object(vec![
+        ("name", string(env!("CARGO_PKG_NAME"))),
+        ("version", string(env!("CARGO_PKG_VERSION"))),
+        ("rules", JsonValue::Array(rules.into())),
+    ]);
+    let run = object(vec![
+        ("tool", object(vec![("driver", driver)])),
+        //columns count characters, not the UTF-16 units SARIF assumes
+        ("columnKind", string("unicodeCodePoints")),
+        ("results", JsonValue::Array(results.into())),
+    ]);
+    object(vec![
+        ("$schema", string(SARIF_SCHEMA)),
+        ("version", string("2.1.0")),
+        ("runs", JsonValue::Array(vec![run].into())),
+    ])
+}
+
+//absolute paths become file:// uris, relative ones are left relative to the
+//root the scan ran from, the way code scanning tools resolve them
+fn artifact_location(path: &str) -> JsonValue<'static> {
+    let absolute = Path::new(path).is_absolute();
+    let path = path.replace('\\', "/");
+    if !absolute {
+        return object(vec![
+            ("uri", string(percent_encode(&path, false))),
+            ("uriBaseId", string("%SRCROOT%")),
+        ]);
+    }
+    //a windows path like C:/dir still needs the slash a unix one starts with
+    let separator = if path.starts_with('/') { "" } else { "/" };
+    object(vec![(
+        "uri",
+        string(format!("file://{separator}{}", percent_encode(&path, true))),
+    )])
+}
+
+//escapes everything but the characters a uri path can hold as they are. A ':'
+//is only kept in absolute paths, for windows drive letters; in a relative one
+//it would be read as the end of a scheme
+fn percent_encode(path: &str, absolute: bool) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) || (absolute && byte == b':') {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+//a stable name for the kind of error, for tools to match on
+fn kind(error: &ParserErrors) -> &'static str {
+    match error {
+        ParserErrors::ArgumentError(_) => "argument_error",
+        ParserErrors::TokenizeError(_) => "tokenize_error",
+        ParserErrors::ParsingError { .. } => "parsing_error",
+        ParserErrors::InvalidEscape { .. } => "invalid_escape",
+        ParserErrors::ControlCharacter { .. } => "control_character",
+        ParserErrors::UnterminatedString { .. } => "unterminated_string",
+        ParserErrors::DepthLimitExceeded { .. } => "depth_limit_exceeded",
+        ParserErrors::IoError(_) => "io_error",
+        ParserErrors::InvalidNumber { .. } => "invalid_number",
+        ParserErrors::InvalidUtf8 { .. } => "invalid_utf8",
+    }
+}
+
+//what went wrong, without the "IO error: " the error itself starts with
+fn message(error: &ParserErrors) -> String {
+    match error {
+        ParserErrors::IoError(error) => error.to_string(),
+        _ => diagnostic::label(error),
+    }
+}
+
+fn object(members: Vec<(&'static str, JsonValue<'static>)>) -> JsonValue<'static> {
+    JsonValue::Object(
+        members
+            .into_iter()
+            .map(|(name, value)| (Cow::Borrowed(name), value))
+            .collect(),
+    )
+}
+
+fn string(text: impl Into<String>) -> JsonValue<'static> {
+    JsonValue::String(Cow::Owned(text.into()))
+}
+
+fn number(value: usize) -> JsonValue<'static> {
+    JsonValue::Number(Number::PosInt(value as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_str, to_string};
+
+    fn results() -> Vec<Result<(), Diagnostic>> {
+        let parsing_error = from_str("[1,]").unwrap_err();
+        vec![
+            Ok(()),
+            Err(Diagnostic {
+                error: parsing_error,
+                rendered: Some("error: invalid json".into()),
+            }),
+            Err(ParserErrors::IoError(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "not found",
+            ))
+            .into()),
+        ]
+    }
+
+    #[test]
+    fn test_json_records() {
+        let results = results();
+        let records: Vec<String> = ["a.json", "b.json", "missing.json"]
+            .iter()
+            .zip(&results)
+            .map(|(path, result)| to_string(&json_record(&Outcome { path, result })))
+            .collect();
+        assert_eq!(
+            records,
+            [
+                r#"{"path":"a.json","valid":true,"kind":null,"line":null,"column":null,"message":null}"#,
+                r#"{"path":"b.json","valid":false,"kind":"parsing_error","line":1,"column":4,"message":"trailing comma before ']'"}"#,
+                r#"{"path":"missing.json","valid":false,"kind":"io_error","line":null,"column":null,"message":"not found"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sarif_log() {
+        let results = results();
+        let outcomes: Vec<Outcome> = ["a.json", "dir\\b.json", "missing.json"]
+            .iter()
+            .zip(&results)
+            .map(|(path, result)| Outcome { path, result })
+            .collect();
+        let log = sarif_log(&outcomes);
+
+        assert_eq!(log.get("version").unwrap().as_str(), Some("2.1.0"));
+        let run = &log.get("runs").unwrap().as_array().unwrap()[0];
+        let rules = run.get("tool").unwrap().get("driver").unwrap().get("rules");
+        let rule_ids: Vec<&str> = rules
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rule| rule.get("id").unwrap().as_str().unwrap())
+            .collect();
+        assert_eq!(rule_ids, ["parsing_error", "io_error"]);
+
+        let results = run.get("results").unwrap().as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            to_string(results[0].get("locations").unwrap()),
+            r#"[{"physicalLocation":{"artifactLocation":{"uri":"dir/b.json","uriBaseId":"%SRCROOT%"},"region":{"startLine":1,"startColumn":4}}}]"#
+        );
+        assert_eq!(
+            to_string(results[1].get("locations").unwrap()),
+            r#"[{"physicalLocation":{"artifactLocation":{"uri":"missing.json","uriBaseId":"%SRCROOT%"}}}]"#
+        );
+        //the log is itself valid json
+        assert_eq!(from_str(&to_string(&log)).unwrap(), log);
+    }
+
+    #[test]
+    fn test_artifact_location() {
+        assert_eq!(
+            to_string(&artifact_location("my dir\\#1 100%:a.json")),
+            r#"{"uri":"my%20dir/%231%20100%25%3Aa.json","uriBaseId":"%SRCROOT%"}"#
+        );
+        assert_eq!(
+            to_string(&artifact_location("caf\u{e9}.json")),
+            r#"{"uri":"caf%C3%A9.json","uriBaseId":"%SRCROOT%"}"#
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            to_string(&artifact_location("/tmp/a b.json")),
+            r#"{"uri":"file:///tmp/a%20b.json"}"#
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            to_string(&artifact_location("C:\\a b.json")),
+            r#"{"uri":"file:///C:/a%20b.json"}"#
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use std::{borrow::Cow, fmt, io};
+
+use crate::{Event, JsonValue, Number};
+
+/// What pretty printed output indents with.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Indent {
+    /// This many spaces per nesting level.
+    Spaces(usize),
+    /// One tab per nesting level.
+    Tab,
+}
+
+/// The line ending of pretty printed output.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Newline {
+    Lf,
+    CrLf,
+}
+
+/// Settings for [`JSONSerializer`]. The default is the compact form.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SerializerOptions {
+    /// Put every member and element on a line of its own, indented by how
+    /// deep it is nested. `None` writes the whole value on one line.
+    pub indent: Option<Indent>,
+    /// The line ending used when indenting.
+    pub newline: Newline,
+    /// Write `"key": value` instead of `"key":value`.
+    pub space_after_colon: bool,
+}
+
+impl SerializerOptions {
+    /// No whitespace at all.
+    pub fn compact() -> SerializerOptions {
+        SerializerOptions {
+            indent: None,
+            newline: Newline::Lf,
+            space_after_colon: false,
+        }
+    }
+
+    /// Two spaces of indent, `\n` line endings and a space after colons.
+    pub fn pretty() -> SerializerOptions {
+        SerializerOptions {
+            indent: Some(Indent::Spaces(2)),
+            newline: Newline::Lf,
+            space_after_colon: true,
+        }
+    }
+}
+
+impl Default for SerializerOptions {
+    fn default() -> Self {
+        SerializerOptions::compact()
+    }
+}
+
+/// Turns a [`JsonValue`] back into JSON text laid out as the
+/// [`SerializerOptions`] say.
+///
+/// ```
+/// use json_parser::{Indent, JSONSerializer, SerializerOptions};
+///
+/// let value = json_parser::from_str("{\"a\": [1, \"two\"]}").unwrap();
+/// let serializer = JSONSerializer::new(SerializerOptions {
+///     indent: Some(Indent::Tab),
+///     ..SerializerOptions::pretty()
+/// });
+/// assert_eq!(
+///     serializer.to_string(&value),
+///     "{\n\t\"a\": [\n\t\t1,\n\t\t\"two\"\n\t]\n}"
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct JSONSerializer {
+    options: SerializerOptions,
+}
+
+impl JSONSerializer {
+    pub fn new(options: SerializerOptions) -> JSONSerializer {
+        JSONSerializer { options }
+    }
+
+    pub fn to_string(&self, value: &JsonValue) -> String {
+        let mut output = String::new();
+        //writing to a String can't fail
+        let _ = EventWriter::new(&mut output, &self.options).write_value(value);
+        output
+    }
+
+    pub fn to_writer(&self, value: &JsonValue, writer: impl io::Write) -> io::Result<()> {
+        let mut writer = IoWriter::new(writer);
+        let result = EventWriter::new(&mut writer, &self.options).write_value(value);
+        writer.finish(result)
+    }
+}
+
+/// Writes `value` as compact JSON text.
+///
+/// ```
+/// let value = json_parser::from_str("{ \"key\" : [ 1 , true ] }").unwrap();
+/// assert_eq!(json_parser::to_string(&value), "{\"key\":[1,true]}");
+/// ```
+pub fn to_string(value: &JsonValue) -> String {
+    JSONSerializer::default().to_string(value)
+}
+
+/// Writes `value` as indented JSON text, see [`SerializerOptions::pretty`].
+pub fn to_string_pretty(value: &JsonValue) -> String {
+    JSONSerializer::new(SerializerOptions::pretty()).to_string(value)
+}
+
+/// Writes `value` as compact JSON text to `writer`.
+pub fn to_writer(writer: impl io::Write, value: &JsonValue) -> io::Result<()> {
+    JSONSerializer::default().to_writer(value, writer)
+}
+
+/// Writes the value as compact JSON text.
+impl fmt::Display for JsonValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        EventWriter::new(f, &SerializerOptions::compact()).write_value(self)
+    }
+}
+
+//writes a value piece by piece in the order the parser reads it, taking care
+//of the commas, line breaks and indent in between
+pub(crate) struct EventWriter<'o, W> {
+    output: W,
+    options: &'o SerializerOptions,
+    //one entry per open list or object: whether anything was written in it
+    open: Vec<bool>,
+    //a key was just written, so its value goes right after the colon
+    after_key: bool,
+}
+
+impl<'o, W: fmt::Write> EventWriter<'o, W> {
+    pub(crate) fn new(output: W, options: &'o SerializerOptions) -> Self {
+        EventWriter {
+            output,
+            options,
+            open: Vec::new(),
+            after_key: false,
+        }
+    }
+
+    pub(crate) fn write_event(&mut self, event: &Event) -> fmt::Result {
+        match event {
+            Event::StartObject => self.start('{'),
+            Event::Key(key) => self.key(key),
+            Event::EndObject => self.end('}'),
+            Event::StartArray => self.start('['),
+            Event::EndArray => self.end(']'),
+            Event::String(string) => self.string(string),
+            Event::Number(number) => self.number(number),
+            Event::Bool(boolean) => self.scalar(if *boolean { "true" } else { "false" }),
+            Event::Null => self.scalar("null"),
+        }
+    }
+
+    //walks the tree with an explicit stack, so deep values don't overflow
+    fn write_value(&mut self, value: &JsonValue) -> fmt::Result {
+        enum Frame<'v, 'a> {
+            List(std::slice::Iter<'v, JsonValue<'a>>),
+            Object(std::slice::Iter<'v, (Cow<'a, str>, JsonValue<'a>)>),
+        }
+        let mut stack: Vec<Frame> = Vec::new();
+        let mut next = Some(value);
+        loop {
+            match next.take() {
+                Some(JsonValue::Array(elements)) => {
+                    self.start('[')?;
+                    stack.push(Frame::List(elements.iter()));
+                }
+                Some(JsonValue::Object(members)) => {
+                    self.start('{')?;
+                    stack.push(Frame::Object(members.iter()));
+                }
+                Some(JsonValue::String(string)) => self.string(string)?,
+                Some(JsonValue::Number(number)) => self.number(number)?,
+                Some(JsonValue::Bool(boolean)) => {
+                    self.scalar(if *boolean { "true" } else { "false" })?
+                }
+                Some(JsonValue::Null) => self.scalar("null")?,
+                None => (),
+            }
+            match stack.last_mut() {
+                None => return Ok(()),
+                Some(Frame::List(elements)) => match elements.next() {
+                    Some(element) => next = Some(element),
+                    None => {
+                        stack.pop();
+                        self.end(']')?;
+                    }
+                },
+                Some(Frame::Object(members)) => match members.next() {
+                    Some((key, value)) => {
+                        self.key(key)?;
+                        next = Some(value);
+                    }
+                    None => {
+                        stack.pop();
+                        self.end('}')?;
+                    }
+                },
+            }
+        }
+    }
+
+    fn start(&mut self, open: char) -> fmt::Result {
+        self.separate()?;
+        self.output.write_char(open)?;
+        self.open.push(false);
+        Ok(())
+    }
+
+    fn end(&mut self, close: char) -> fmt::Result {
+        //empty lists and objects stay on one line
+        if self.open.pop() == Some(true) {
+            self.line_break()?;
+        }
+        self.output.write_char(close)
+    }
+
+    fn key(&mut self, key: &str) -> fmt::Result {
+        self.separate()?;
+        write_string(&mut self.output, key)?;
+        self.output.write_char(':')?;
+        if self.options.space_after_colon {
+            self.output.write_char(' ')?;
+        }
+        self.after_key = true;
+        Ok(())
+    }
+
+    fn string(&mut self, string: &str) -> fmt::Result {
+        self.separate()?;
+        write_string(&mut self.output, string)
+    }
+
+    fn number(&mut self, number: &Number) -> fmt::Result {
+        self.separate()?;
+        match number {
+            Number::PosInt(integer) => write!(self.output, "{integer}"),
+            Number::NegInt(integer) => write!(self.output, "{integer}"),
+            //Debug keeps the fraction of round floats and uses exponents for
+            //huge or tiny ones, both valid JSON. There is no JSON for NaN or
+            //the infinities
+            Number::Float(float) if float.is_finite() => write!(self.output, "{float:?}"),
+            Number::Float(_) => self.output.write_str("null"),
+            Number::Raw(raw) => self.output.write_str(raw),
+        }
+    }
+
+    fn scalar(&mut self, text: &str) -> fmt::Result {
+        self.separate()?;
+        self.output.write_str(text)
+    }
+
+    //the comma and line break in front of a key or an element
+    fn separate(&mut self) -> fmt::Result {
+        if self.after_key {
+            self.after_key = false;
+            return Ok(());
+        }
+        let Some(written) = self.open.last_mut() else {
+            return Ok(());
+        };
+        if *written {
+            self.output.write_char(',')?;
+        }
+        *written = true;
+        self.line_break()
+    }
+
+    //starts a new line indented to the current depth, when pretty printing
+    fn line_break(&mut self) -> fmt::Result {
+        let Some(indent) = self.options.indent else {
+            return Ok(());
+        };
+        self.output.write_str(match self.options.newline {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        })?;
+        for _ in 0..self.open.len() {
+            match indent {
+                Indent::Spaces(width) => write!(self.output, "{:width$}", "")?,
+                Indent::Tab => self.output.write_char('\t')?,
+            }
+        }
+        Ok(())
+    }
+}
+
+//writes a quoted string, escaping what JSON requires: quotes, backslashes and
+//control characters
+fn write_string(output: &mut impl fmt::Write, string: &str) -> fmt::Result {
+    output.write_char('"')?;
+    let mut unescaped = 0;
+    for (index, c) in string.char_indices() {
+        let escape = match c {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            '\u{8}' => "\\b",
+            '\u{c}' => "\\f",
+            '\u{0}'..='\u{1f}' => "",
+            _ => continue,
+        };
+        output.write_str(&string[unescaped..index])?;
+        if escape.is_empty() {
+            write!(output, "\\u{:04x}", c as u32)?;
+        } else {
+            output.write_str(escape)?;
+        }
+        unescaped = index + c.len_utf8();
+    }
+    output.write_str(&string[unescaped..])?;
+    output.write_char('"')
+}
+
+//lets the writer, which works on fmt::Write, write to an io::Write, keeping
+//the io error fmt::Error has no room for
+pub(crate) struct IoWriter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> IoWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        IoWriter { inner, error: None }
+    }
+
+    pub(crate) fn finish(mut self, result: fmt::Result) -> io::Result<()> {
+        match (result, self.error) {
+            (Ok(()), _) => self.inner.flush(),
+            (Err(_), Some(error)) => Err(error),
+            (Err(_), None) => Err(io::Error::other("formatter error")),
+        }
+    }
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.inner.write_all(text.as_bytes()).map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    const DOCUMENT: &str = r#"{"name": "app", "ports": [80, 443], "empty": {}, "none": [], "nested": {"deep": [null, -1.5, true]}}"#;
+
+    #[test]
+    fn test_to_string_compact() {
+        let value = from_str(DOCUMENT).unwrap();
+        assert_eq!(
+            to_string(&value),
+            r#"{"name":"app","ports":[80,443],"empty":{},"none":[],"nested":{"deep":[null,-1.5,true]}}"#
+        );
+        assert_eq!(value.to_string(), to_string(&value));
+        assert_eq!(to_string(&JsonValue::Bool(false)), "false");
+    }
+
+    #[test]
+    fn test_to_string_pretty() {
+        let value = from_str(DOCUMENT).unwrap();
+        assert_eq!(
+            to_string_pretty(&value),
+            "{\n  \"name\": \"app\",\n  \"ports\": [\n    80,\n    443\n  ],\n  \
+             \"empty\": {},\n  \"none\": [],\n  \"nested\": {\n    \"deep\": [\n      \
+             null,\n      -1.5,\n      true\n    ]\n  }\n}"
+        );
+    }
+
+    #[test]
+    fn test_serializer_options() {
+        let value = from_str(r#"{"a": [1], "b": {}}"#).unwrap();
+        let serializer = JSONSerializer::new(SerializerOptions {
+            indent: Some(Indent::Spaces(4)),
+            newline: Newline::CrLf,
+            space_after_colon: false,
+        });
+        assert_eq!(
+            serializer.to_string(&value),
+            "{\r\n    \"a\":[\r\n        1\r\n    ],\r\n    \"b\":{}\r\n}"
+        );
+
+        let serializer = JSONSerializer::new(SerializerOptions {
+            space_after_colon: true,
+            ..SerializerOptions::compact()
+        });
+        assert_eq!(serializer.to_string(&value), r#"{"a": [1],"b": {}}"#);
+    }
+
+    #[test]
+    fn test_strings_are_escaped() {
+        let value = JsonValue::Array(
+            vec![
+                JsonValue::String("quote \" backslash \\ slash /".into()),
+                JsonValue::String("\n\r\t\u{8}\u{c}\u{0}\u{1f}".into()),
+                JsonValue::String("é中😀".into()),
+            ]
+            .into(),
+        );
+        let text = to_string(&value);
+        assert_eq!(
+            text,
+            r#"["quote \" backslash \\ slash /","\n\r\t\b\f\u0000\u001f","é中😀"]"#
+        );
+        assert_eq!(from_str(&text).unwrap(), value);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let value = JsonValue::Array(
+            vec![
+                JsonValue::Number(Number::PosInt(u64::MAX)),
+                JsonValue::Number(Number::NegInt(i64::MIN)),
+                JsonValue::Number(Number::Float(1.0)),
+                JsonValue::Number(Number::Float(1e40)),
+                JsonValue::Number(Number::Float(-2.5e-8)),
+                JsonValue::Number(Number::Float(f64::NAN)),
+                JsonValue::Number(Number::Raw("1.000e400".into())),
+            ]
+            .into(),
+        );
+        assert_eq!(
+            to_string(&value),
+            "[18446744073709551615,-9223372036854775808,1.0,1e40,-2.5e-8,null,1.000e400]"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = std::fs::read_to_string("test_data/JSON_checker/pass1.json").unwrap();
+        let value = from_str(&input).unwrap();
+        assert_eq!(from_str(&to_string(&value)).unwrap(), value);
+        assert_eq!(from_str(&to_string_pretty(&value)).unwrap(), value);
+    }
+
+    #[test]
+    fn test_to_writer() {
+        let value = from_str("[1, {\"a\": null}]").unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        to_writer(&mut output, &value).unwrap();
+        assert_eq!(output, b"[1,{\"a\":null}]");
+
+        //errors of the writer come back as they are
+        let mut full = [0u8; 4];
+        let error = to_writer(&mut full[..], &value).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn test_deep_value() {
+        let mut value = JsonValue::Null;
+        for _ in 0..200_000 {
+            value = JsonValue::Array(vec![value].into());
+        }
+        let text = to_string(&value);
+        assert_eq!(text.len(), 200_000 * 2 + 4);
+        assert!(text.starts_with("[[[[") && text.ends_with("]]]]"));
+        assert_eq!(text.find("null"), Some(200_000));
+    }
+}
@@ -0,0 +1,509 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+/// A JSON value as built by the parser.
+///
+/// Object members are kept in document order. Strings and keys parsed from a
+/// `&str` borrow from it unless they had escapes to decode, values read from
+/// anything else own their strings and are `JsonValue<'static>`.
+///
+/// Lists and objects are [`Array`] and [`Object`], which drop their children
+/// without recursing, so however deep a value is nested it can be dropped.
+/// Cloning and comparing values still recurse once per nesting level.
+#[derive(Debug, PartialEq, Clone)]
+pub enum JsonValue<'a> {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(Cow<'a, str>),
+    Array(Array<'a>),
+    Object(Object<'a>),
+}
+
+/// The elements of a JSON list.
+///
+/// It derefs to the `Vec` holding them and converts from and into one.
+///
+/// ```
+/// use json_parser::{Array, JsonValue};
+///
+/// let list = JsonValue::Array(vec![JsonValue::Null].into());
+/// let JsonValue::Array(elements) = list else {
+///     unreachable!()
+/// };
+/// let elements: Vec<JsonValue> = elements.into_vec();
+/// assert_eq!(elements, [JsonValue::Null]);
+/// assert!(Array::default().is_empty());
+/// ```
+#[derive(PartialEq, Clone, Default)]
+pub struct Array<'a>(Vec<JsonValue<'a>>);
+
+/// The members of a JSON object, in document order.
+///
+/// Like [`Array`] it derefs to the `Vec` holding them and converts from and
+/// into one.
+#[derive(PartialEq, Clone, Default)]
+pub struct Object<'a>(Vec<(Cow<'a, str>, JsonValue<'a>)>);
+
+//implements everything Array and Object have in common for the type of their
+//items
+macro_rules! container {
+    ($container:ident, $item:ty) => {
+        impl<'a> $container<'a> {
+            pub fn new() -> Self {
+                $container(Vec::new())
+            }
+
+            /// Takes the items out.
+            pub fn into_vec(mut self) -> Vec<$item> {
+                std::mem::take(&mut self.0)
+            }
+        }
+
+        impl<'a> Deref for $container<'a> {
+            type Target = Vec<$item>;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl DerefMut for $container<'_> {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+
+        impl<'a> From<Vec<$item>> for $container<'a> {
+            fn from(items: Vec<$item>) -> Self {
+                $container(items)
+            }
+        }
+
+        impl<'a> From<$container<'a>> for Vec<$item> {
+            fn from(container: $container<'a>) -> Self {
+                container.into_vec()
+            }
+        }
+
+        impl<'a> FromIterator<$item> for $container<'a> {
+            fn from_iter<I: IntoIterator<Item = $item>>(items: I) -> Self {
+                $container(items.into_iter().collect())
+            }
+        }
+
+        impl<'a> IntoIterator for $container<'a> {
+            type Item = $item;
+            type IntoIter = std::vec::IntoIter<$item>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.into_vec().into_iter()
+            }
+        }
+
+        impl<'c, 'a> IntoIterator for &'c $container<'a> {
+            type Item = &'c $item;
+            type IntoIter = std::slice::Iter<'c, $item>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.0.iter()
+            }
+        }
+
+        impl<'c, 'a> IntoIterator for &'c mut $container<'a> {
+            type Item = &'c mut $item;
+            type IntoIter = std::slice::IterMut<'c, $item>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.0.iter_mut()
+            }
+        }
+
+        //shows just the items, like the Vec it stands for
+        impl fmt::Debug for $container<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    };
+}
+
+container!(Array, JsonValue<'a>);
+container!(Object, (Cow<'a, str>, JsonValue<'a>));
+
+//the default drop recurses once per nesting level, which overflows the stack
+//for the deep documents the parser is able to build. Lists and objects move
+//their nested children onto a heap allocated stack instead and empty each one
+//before it is dropped
+impl Drop for Array<'_> {
+    fn drop(&mut self) {
+        if self.0.iter().any(is_nested) {
+            drop_nested(std::mem::take(&mut self.0));
+        }
+    }
+}
+
+impl Drop for Object<'_> {
+    fn drop(&mut self) {
+        if self.0.iter().any(|(_, value)| is_nested(value)) {
+            drop_nested(
+                std::mem::take(&mut self.0)
+                    .into_iter()
+                    .map(|(_, value)| value),
+            );
+        }
+    }
+}
+
+fn is_nested(value: &JsonValue) -> bool {
+    matches!(value, JsonValue::Array(elements) if !elements.is_empty())
+        || matches!(value, JsonValue::Object(members) if !members.is_empty())
+}
+
+fn drop_nested<'a>(values: impl IntoIterator<Item = JsonValue<'a>>) {
+    let mut stack: Vec<JsonValue<'a>> = values.into_iter().filter(is_nested).collect();
+    while let Some(mut value) = stack.pop() {
+        match &mut value {
+            JsonValue::Array(elements) => stack.extend(elements.drain(..).filter(is_nested)),
+            JsonValue::Object(members) => {
+                stack.extend(members.drain(..).map(|(_, value)| value).filter(is_nested))
+            }
+            _ => (),
+        }
+    }
+}
+
+/// A JSON number as read from the document.
+///
+/// Integers are kept as integers as long as they fit in 64 bits, everything
+/// else becomes a float unless the parser was asked for arbitrary precision.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Number {
+    /// A non-negative integer.
+    PosInt(u64),
+    /// A negative integer.
+    NegInt(i64),
+    Float(f64),
+    /// The number exactly as written in the document.
+    Raw(String),
+}
+
+impl Number {
+    //converts a literal that already follows the JSON number grammar, None
+    //when it does not fit in a finite f64
+    pub(crate) fn from_literal(literal: &str, arbitrary_precision: bool) -> Option<Number> {
+        if arbitrary_precision {
+            return Some(Number::Raw(literal.to_string()));
+        }
+        if !literal.contains(['.', 'e', 'E']) {
+            if let Ok(integer) = literal.parse::<u64>() {
+                return Some(Number::PosInt(integer));
+            }
+            //`-0` is left to the float below, which keeps its sign
+            if let Ok(integer @ ..0) = literal.parse::<i64>() {
+                return Some(Number::NegInt(integer));
+            }
+        }
+        let float = literal.parse::<f64>().ok()?;
+        float.is_finite().then_some(Number::Float(float))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        match self {
+            Number::PosInt(_) | Number::NegInt(_) => true,
+            Number::Float(_) => false,
+            Number::Raw(raw) => !raw.contains(['.', 'e', 'E']),
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Number::PosInt(integer) => Some(*integer as f64),
+            Number::NegInt(integer) => Some(*integer as f64),
+            Number::Float(float) => Some(*float),
+            Number::Raw(raw) => raw.parse::<f64>().ok().filter(|float| float.is_finite()),
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Number::PosInt(integer) => i64::try_from(*integer).ok(),
+            Number::NegInt(integer) => Some(*integer),
+            Number::Float(_) => None,
+            Number::Raw(raw) => raw.parse::<i64>().ok(),
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Number::PosInt(integer) => Some(*integer),
+            Number::NegInt(_) | Number::Float(_) => None,
+            Number::Raw(raw) => raw.parse::<u64>().ok(),
+        }
+    }
+}
+
+impl<'a> JsonValue<'a> {
+    /// Looks up `key` in an object, returning the last member with that name.
+    pub fn get(&self, key: &str) -> Option<&JsonValue<'a>> {
+        match self {
+            JsonValue::Object(members) => members
+                .iter()
+                .rev()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == JsonValue::Null
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(boolean) => Some(*boolean),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<&Number> {
+        match self {
+            JsonValue::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue<'a>]> {
+        match self {
+            JsonValue::Array(elements) => Some(&elements[..]),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(Cow<'a, str>, JsonValue<'a>)]> {
+        match self {
+            JsonValue::Object(members) => Some(&members[..]),
+            _ => None,
+        }
+    }
+
+    /// Copies every borrowed string so the value no longer depends on the
+    /// input it was parsed from.
+    pub fn into_owned(mut self) -> JsonValue<'static> {
+        //like the parser, the open lists and objects go on a heap allocated
+        //stack so deep values don't overflow the call stack
+        let mut stack: Vec<Owning<'a>> = Vec::new();
+        loop {
+            let mut done = match &mut self {
+                JsonValue::Null => Some(JsonValue::Null),
+                JsonValue::Bool(boolean) => Some(JsonValue::Bool(*boolean)),
+                JsonValue::Number(number) => Some(JsonValue::Number(number.clone())),
+                JsonValue::String(string) => Some(JsonValue::String(Cow::Owned(
+                    std::mem::take(string).into_owned(),
+                ))),
+                JsonValue::Array(elements) => {
+                    let elements = std::mem::take(elements);
+                    let owned = Vec::with_capacity(elements.len());
+                    stack.push(Owning::List(elements.into_iter(), owned));
+                    None
+                }
+                JsonValue::Object(members) => {
+                    let members = std::mem::take(members);
+                    let owned = Vec::with_capacity(members.len());
+                    stack.push(Owning::Object(members.into_iter(), owned, None));
+                    None
+                }
+            };
+            //hand the finished value to its parent and find the next child
+            loop {
+                let Some(top) = stack.last_mut() else {
+                    return done.unwrap_or(JsonValue::Null);
+                };
+                if let Some(value) = done.take() {
+                    top.push(value);
+                }
+                if let Some(child) = top.next_child() {
+                    self = child;
+                    break;
+                }
+                done = stack.pop().map(Owning::finish);
+            }
+        }
+    }
+}
+
+//a list or object whose children are being copied by into_owned
+enum Owning<'a> {
+    List(std::vec::IntoIter<JsonValue<'a>>, Vec<JsonValue<'static>>),
+    Object(
+        std::vec::IntoIter<(Cow<'a, str>, JsonValue<'a>)>,
+        Vec<(Cow<'static, str>, JsonValue<'static>)>,
+        //the key of the member being copied
+        Option<Cow<'static, str>>,
+    ),
+}
+
+impl<'a> Owning<'a> {
+    fn next_child(&mut self) -> Option<JsonValue<'a>> {
+        match self {
+            Owning::List(elements, _) => elements.next(),
+            Owning::Object(members, _, key) => {
+                let (name, value) = members.next()?;
+                *key = Some(Cow::Owned(name.into_owned()));
+                Some(value)
+            }
+        }
+    }
+
+    fn push(&mut self, value: JsonValue<'static>) {
+        match self {
+            Owning::List(_, owned) => owned.push(value),
+            Owning::Object(_, owned, key) => owned.push((key.take().unwrap_or_default(), value)),
+        }
+    }
+
+    fn finish(self) -> JsonValue<'static> {
+        match self {
+            Owning::List(_, owned) => JsonValue::Array(owned.into()),
+            Owning::Object(_, owned, _) => JsonValue::Object(owned.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_returns_last_duplicate_key() {
+        let value = JsonValue::Object(
+            vec![
+                ("key".into(), JsonValue::Bool(true)),
+                ("other".into(), JsonValue::Null),
+                ("key".into(), JsonValue::Bool(false)),
+            ]
+            .into(),
+        );
+
+        assert_eq!(value.get("key"), Some(&JsonValue::Bool(false)));
+        assert_eq!(value.get("missing"), None);
+        assert_eq!(JsonValue::Null.get("key"), None);
+    }
+
+    #[test]
+    fn test_number_from_literal() {
+        assert_eq!(Number::from_literal("14", false), Some(Number::PosInt(14)));
+        assert_eq!(
+            Number::from_literal("-32", false),
+            Some(Number::NegInt(-32))
+        );
+        assert_eq!(
+            Number::from_literal("18446744073709551615", false),
+            Some(Number::PosInt(u64::MAX))
+        );
+        //too big for 64 bits falls back to a float
+        assert_eq!(
+            Number::from_literal("18446744073709551616", false),
+            Some(Number::Float(18446744073709551616.0))
+        );
+        assert_eq!(
+            Number::from_literal("-3.2", false),
+            Some(Number::Float(-3.2))
+        );
+        let negative_zero = Number::from_literal("-0", false);
+        assert!(
+            matches!(negative_zero, Some(Number::Float(zero)) if zero == 0.0 && zero.is_sign_negative())
+        );
+        assert_eq!(Number::from_literal("0", false), Some(Number::PosInt(0)));
+        assert_eq!(Number::from_literal("1e400", false), None);
+        assert_eq!(
+            Number::from_literal("1e400", true),
+            Some(Number::Raw("1e400".into()))
+        );
+    }
+
+    #[test]
+    fn test_number_conversions() {
+        assert_eq!(Number::NegInt(-32).as_i64(), Some(-32));
+        assert_eq!(Number::NegInt(-32).as_u64(), None);
+        assert_eq!(Number::PosInt(u64::MAX).as_i64(), None);
+        assert_eq!(Number::PosInt(u64::MAX).as_u64(), Some(u64::MAX));
+        assert_eq!(Number::Float(0.5).as_i64(), None);
+        assert_eq!(Number::Float(0.5).as_f64(), Some(0.5));
+
+        let raw = Number::Raw("12345678901234567890123".into());
+        assert!(raw.is_integer());
+        assert_eq!(raw.as_u64(), None);
+        assert_eq!(raw.as_f64(), Some(12345678901234567890123.0));
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let input = String::from("key");
+        let value = JsonValue::Object(
+            vec![
+                (
+                    Cow::Borrowed(input.as_str()),
+                    JsonValue::Array(
+                        vec![
+                            JsonValue::String(Cow::Borrowed(&input[1..])),
+                            JsonValue::Array(vec![].into()),
+                            JsonValue::Null,
+                        ]
+                        .into(),
+                    ),
+                ),
+                ("other".into(), JsonValue::Object(vec![].into())),
+            ]
+            .into(),
+        );
+        let expected = value.clone();
+        let owned: JsonValue<'static> = value.into_owned();
+
+        assert_eq!(owned, expected);
+        assert!(matches!(&owned.as_object().unwrap()[0].0, Cow::Owned(_)));
+        assert!(matches!(
+            &owned.get("key").unwrap().as_array().unwrap()[0],
+            JsonValue::String(Cow::Owned(_))
+        ));
+    }
+
+    #[test]
+    fn test_into_owned_deep_value() {
+        let mut value = JsonValue::Null;
+        for _ in 0..200_000 {
+            value = JsonValue::Array(vec![value].into());
+        }
+        let owned = value.into_owned();
+        let mut depth = 0;
+        let mut inner = &owned;
+        while let Some([element]) = inner.as_array() {
+            inner = element;
+            depth += 1;
+        }
+        assert_eq!(depth, 200_000);
+    }
+
+    #[test]
+    fn test_values_can_be_moved_out_of() {
+        let value = JsonValue::Array(vec![JsonValue::String("a".into())].into());
+        let JsonValue::Array(mut elements) = value else {
+            panic!("not a list");
+        };
+        let JsonValue::String(string) = elements.remove(0) else {
+            panic!("not a string");
+        };
+        assert_eq!(string, "a");
+    }
+}
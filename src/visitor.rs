@@ -0,0 +1,137 @@
+use crate::{Event, JsonValue, Span};
+
+/// Callbacks the parser calls as it reads a document, for processing it
+/// without building a [`JsonValue`] tree.
+///
+/// Every callback does nothing by default, so only the ones of interest have
+/// to be written. Strings, numbers, booleans and null all go to
+/// [`on_value`](JsonVisitor::on_value); inside an object each value
+/// comes right after its key.
+///
+/// ```
+/// use json_parser::{JSONDocument, JsonValue, JsonVisitor, Span};
+///
+/// #[derive(Default)]
+/// struct Sum(f64);
+/// impl JsonVisitor for Sum {
+///     fn on_value(&mut self, value: &JsonValue, _span: Span) {
+///         if let Some(number) = value.as_number().and_then(|number| number.as_f64()) {
+///             self.0 += number;
+///         }
+///     }
+/// }
+///
+/// let mut sum = Sum::default();
+/// JSONDocument::default()
+///     .visit_str("{\"a\": 1, \"b\": [2, 3.5]}", &mut sum)
+///     .unwrap();
+/// assert_eq!(sum.0, 6.5);
+/// ```
+pub trait JsonVisitor {
+    fn on_object_start(&mut self, _span: Span) {}
+
+    fn on_key(&mut self, _key: &str, _span: Span) {}
+
+    fn on_object_end(&mut self, _span: Span) {}
+
+    fn on_array_start(&mut self, _span: Span) {}
+
+    fn on_array_end(&mut self, _span: Span) {}
+
+    /// Called for every string, number, boolean and null.
+    fn on_value(&mut self, _value: &JsonValue, _span: Span) {}
+}
+
+//hands an event to the matching callback
+pub(crate) fn dispatch(visitor: &mut impl JsonVisitor, event: Event, span: Span) {
+    let value = match event {
+        Event::StartObject => return visitor.on_object_start(span),
+        Event::Key(key) => return visitor.on_key(&key, span),
+        Event::EndObject => return visitor.on_object_end(span),
+        Event::StartArray => return visitor.on_array_start(span),
+        Event::EndArray => return visitor.on_array_end(span),
+        Event::String(string) => JsonValue::String(string),
+        Event::Number(number) => JsonValue::Number(number),
+        Event::Bool(boolean) => JsonValue::Bool(boolean),
+        Event::Null => JsonValue::Null,
+    };
+    visitor.on_value(&value, span);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JSONDocument;
+
+    //writes down the path of every value, like an indexer would
+    #[derive(Default)]
+    struct Paths {
+        path: Vec<String>,
+        //whether each open container is an object, whose key has to be
+        //dropped after its value
+        objects: Vec<bool>,
+        found: Vec<(String, usize)>,
+    }
+
+    impl Paths {
+        fn value_done(&mut self) {
+            if self.objects.last() == Some(&true) {
+                self.path.pop();
+            }
+        }
+    }
+
+    impl JsonVisitor for Paths {
+        fn on_object_start(&mut self, _span: Span) {
+            self.objects.push(true);
+        }
+
+        fn on_key(&mut self, key: &str, _span: Span) {
+            self.path.push(key.to_string());
+        }
+
+        fn on_object_end(&mut self, _span: Span) {
+            self.objects.pop();
+            self.value_done();
+        }
+
+        fn on_array_start(&mut self, _span: Span) {
+            self.objects.push(false);
+        }
+
+        fn on_array_end(&mut self, _span: Span) {
+            self.objects.pop();
+            self.value_done();
+        }
+
+        fn on_value(&mut self, _value: &JsonValue, span: Span) {
+            self.found.push((self.path.join("."), span.start.offset));
+            self.value_done();
+        }
+    }
+
+    #[test]
+    fn test_visitor_sees_every_value() {
+        let mut paths = Paths::default();
+        JSONDocument::default()
+            .visit_str(r#"{"a": {"b": [1, 2]}, "c": null, "d": {}}"#, &mut paths)
+            .unwrap();
+
+        assert_eq!(
+            paths.found,
+            vec![("a.b".into(), 13), ("a.b".into(), 16), ("c".into(), 26)]
+        );
+        assert!(paths.path.is_empty());
+    }
+
+    #[test]
+    fn test_visitor_stops_at_the_first_error() {
+        let mut paths = Paths::default();
+        let error = JSONDocument::default()
+            .visit_reader(r#"{"a": 1, "b" 2}"#.as_bytes(), &mut paths)
+            .unwrap_err();
+
+        assert_eq!(error.position().unwrap().offset, 13);
+        assert_eq!(paths.found, vec![("a".into(), 6)]);
+    }
+}
@@ -0,0 +1,77 @@
+//runs every file in test_data through the public api, files named pass* or
+//valid* have to parse and files named fail* or invalid* have to be rejected.
+//Valid files also have to come back unchanged out of a syntax tree
+use std::{fs, path::Path};
+
+use json_parser::{JSONDocument, ParserOptions};
+
+fn check_dir(dir: &str, json_document: &JSONDocument) -> usize {
+    let mut paths: Vec<_> = fs::read_dir(Path::new("test_data").join(dir))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+
+    let mut checked = 0;
+    let mut failures: Vec<String> = Vec::new();
+    for path in paths {
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let expect_valid = name.starts_with("pass") || name.starts_with("valid");
+        assert!(
+            expect_valid || name.starts_with("fail") || name.starts_with("invalid"),
+            "{} is neither a pass nor a fail case",
+            path.display()
+        );
+
+        let data = fs::read(&path).unwrap();
+        let result = json_document.parse_slice(&data);
+        match (expect_valid, result) {
+            (true, Err(error)) => failures.push(format!("{}: {error}", path.display())),
+            (true, Ok(_)) => {
+                let text = String::from_utf8(data).unwrap();
+                let tree = json_document.parse_cst(&text).unwrap();
+                if tree.to_string() != text {
+                    failures.push(format!("{}: changed by the syntax tree", path.display()));
+                }
+            }
+            (false, Ok(_)) => failures.push(format!("{}: accepted", path.display())),
+            _ => (),
+        }
+        checked += 1;
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+    checked
+}
+
+#[test]
+fn test_json_checker() {
+    //fail1 is a bare string, which JSON_checker rejects following RFC 4627,
+    //and fail18 nests 20 lists deep while JSON_checker allows 19
+    let json_document = JSONDocument::new(ParserOptions {
+        allow_scalar_root: false,
+        max_depth: Some(19),
+        ..Default::default()
+    });
+    assert_eq!(check_dir("JSON_checker", &json_document), 36);
+}
+
+#[test]
+fn test_step1() {
+    assert_eq!(check_dir("step1", &JSONDocument::default()), 2);
+}
+
+#[test]
+fn test_step2() {
+    assert_eq!(check_dir("step2", &JSONDocument::default()), 4);
+}
+
+#[test]
+fn test_step3() {
+    assert_eq!(check_dir("step3", &JSONDocument::default()), 2);
+}
+
+#[test]
+fn test_step4() {
+    assert_eq!(check_dir("step4", &JSONDocument::default()), 3);
+}